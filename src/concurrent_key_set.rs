@@ -0,0 +1,313 @@
+use std::borrow::{ Borrow };
+use std::collections::{ HashMap };
+use std::collections::hash_map::{ self, RandomState };
+use std::hash::{ BuildHasher, Hash };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::sync::{ PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard };
+use std::thread;
+use std::vec;
+use std::fmt;
+
+use crate::key_set::{ GetKey, GetKeyType, DuplicatePolicy, InsertOutcome };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ConcurrentKeySet
+
+/// A key set shared between threads, every method takes `&self`.
+///
+/// The elements are spread over shards by key hash, each shard behind its own
+/// `RwLock`, so threads working on different shards don't wait on each other.
+/// A method locks a single shard, except `snapshot` which locks all of them.
+///
+/// A panic inside a user callback doesn't poison the set: the lock is taken
+/// over and the shard is still consistent. Callbacks run under a shard lock
+/// and must not call back into the set.
+pub struct ConcurrentKeySet<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    duplicate_policy: DuplicatePolicy<T>,
+    hasher: RandomState,
+    shards: Box<[RwLock<HashMap<K, T>>]>,
+}
+
+impl<T, K> ConcurrentKeySet<T, K> where K: Eq + Hash {
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+}
+
+impl<T, K, F> ConcurrentKeySet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    /// Set with four shards per available core.
+    pub fn with_get_key(get_key: F) -> Self {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+
+        Self::with_shard_count(get_key, cores * 4)
+    }
+
+    /// Panics if `shard_count` is zero.
+    pub fn with_shard_count(get_key: F, shard_count: usize) -> Self {
+        assert!(shard_count > 0, "a ConcurrentKeySet needs at least one shard");
+
+        ConcurrentKeySet {
+            get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            hasher: RandomState::new(),
+            shards: (0..shard_count).map(|_| RwLock::new(HashMap::new())).collect(),
+        }
+    }
+
+    pub fn from_intoiter_with(get_key: F, shard_count: usize, iter: impl IntoIterator<Item=T>) -> Self {
+        let this = Self::with_shard_count(get_key, shard_count);
+
+        for value in iter {
+            this.insert(value);
+        }
+        this
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy<T> {
+        self.duplicate_policy
+    }
+
+    /// Takes `&mut self`, the policy is read without locking.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy<T>) {
+        self.duplicate_policy = policy;
+    }
+
+    pub fn insert(&self, value: T) -> InsertOutcome<T> {
+        let key = self.get_key.get_key(&value);
+        let mut shard = self.write_shard(&key);
+
+        match shard.entry(key) {
+            hash_map::Entry::Occupied(mut entry) => {
+                let outcome = self.duplicate_policy.resolve(entry.get_mut(), value);
+
+                if let InsertOutcome::Merged = outcome {
+                    if self.get_key.get_key(entry.get()) != *entry.key() {
+                        entry.remove();
+                        drop(shard);
+                        panic!("the key of the element was changed by DuplicatePolicy::Merge");
+                    }
+                }
+                outcome
+            },
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                InsertOutcome::Inserted
+            },
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.contains_key(&self.get_key.get_key(value))
+    }
+
+    pub fn remove(&self, value: &T) -> bool {
+        self.remove_by_key(&self.get_key.get_key(value))
+    }
+
+    pub fn take(&self, value: &T) -> Option<T> {
+        self.take_by_key(&self.get_key.get_key(value))
+    }
+
+    /// A clone of the element with the key of `value`, taken under the read lock.
+    pub fn get_cloned(&self, value: &T) -> Option<T> where T: Clone {
+        self.get_cloned_by_key(&self.get_key.get_key(value))
+    }
+
+    /// Modify the element of `key` in place under the write lock of its shard.
+    ///
+    /// The element stays in the shard while `f` runs, a panic in `f` leaves it there
+    /// with whatever changes were made. Panics if `f` changes the key, the element is dropped,
+    /// and a panic in `f` after changing the key drops it too.
+    ///
+    /// `f` must not call back into the same set, the shard is write locked and
+    /// touching it again from `f` deadlocks.
+    pub fn update_with<Q, R, U>(&self, key: &Q, f: U) -> Option<R>
+    where K: Borrow<Q>, Q: ?Sized + Eq + Hash, U: FnOnce(&mut T) -> R
+    {
+        let mut shard = self.write_shard(key);
+        let check = KeyCheck { shard: &mut shard, key, get_key: &self.get_key };
+
+        let res = f(check.shard.get_mut(key)?);
+        let changed = check.changed();
+
+        drop(check);
+        drop(shard);
+        if changed {
+            panic!("the key of the element was changed through update_with");
+        }
+
+        Some(res)
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> ConcurrentKeySet<T, K, F> where K: Eq + Hash {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.read_shard(key).contains_key(key)
+    }
+
+    pub fn get_cloned_by_key<Q>(&self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash, T: Clone {
+        self.read_shard(key).get(key).cloned()
+    }
+
+    pub fn remove_by_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.take_by_key(key).is_some()
+    }
+
+    pub fn take_by_key<Q>(&self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.write_shard(key).remove(key)
+    }
+
+    fn shard_of<Q>(&self, key: &Q) -> &RwLock<HashMap<K, T>> where Q: ?Sized + Hash {
+        let hash = self.hasher.hash_one(key);
+
+        &self.shards[(hash % self.shards.len() as u64) as usize]
+    }
+
+    fn read_shard<Q>(&self, key: &Q) -> RwLockReadGuard<'_, HashMap<K, T>> where Q: ?Sized + Hash {
+        self.shard_of(key).read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_shard<Q>(&self, key: &Q) -> RwLockWriteGuard<'_, HashMap<K, T>> where Q: ?Sized + Hash {
+        self.shard_of(key).write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T, K, F> ConcurrentKeySet<T, K, F> {
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Sum of the shard lengths, each read at a different time.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().unwrap_or_else(PoisonError::into_inner).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.write().unwrap_or_else(PoisonError::into_inner).clear();
+        }
+    }
+
+    /// Iterate a clone of the elements at a single point in time.
+    ///
+    /// Every shard is read locked, in order, before any is cloned, so the snapshot
+    /// never mixes the states of the set before and after a write.
+    pub fn snapshot(&self) -> Snapshot<T> where T: Clone {
+        let shards: Vec<RwLockReadGuard<'_, HashMap<K, T>>> = self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+
+        let values: Vec<T> = shards.iter().flat_map(|shard| shard.values().cloned()).collect();
+
+        Snapshot {
+            iter: values.into_iter(),
+        }
+    }
+}
+
+/// IntoIterator for ConcurrentKeySet
+impl<T, K, F> IntoIterator for ConcurrentKeySet<T, K, F> {
+    type Item = T;
+    type IntoIter = IntoIter<T, K>;
+
+    fn into_iter(self) -> IntoIter<T, K> {
+        let shards: Vec<HashMap<K, T>> = self.shards
+            .into_vec()
+            .into_iter()
+            .map(|shard| shard.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+
+        IntoIter {
+            iter: shards.into_iter().flat_map(HashMap::into_values as fn(_) -> _),
+        }
+    }
+}
+
+/// Debug for ConcurrentKeySet
+impl<T, K, F> fmt::Debug for ConcurrentKeySet<T, K, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrentKeySet")
+         .field("shard_count", &self.shards.len())
+         .field("len", &self.len())
+         .finish_non_exhaustive()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyCheck
+
+/// Takes the element of `update_with` out of its shard when dropped with a changed key,
+/// on unwinding included.
+struct KeyCheck<'a, T, K, F, Q: ?Sized> where K: Eq + Hash + Borrow<Q>, Q: Eq + Hash, F: GetKey<T, K> {
+    shard: &'a mut HashMap<K, T>,
+    key: &'a Q,
+    get_key: &'a F,
+}
+
+impl<T, K, F, Q: ?Sized> KeyCheck<'_, T, K, F, Q> where K: Eq + Hash + Borrow<Q>, Q: Eq + Hash, F: GetKey<T, K> {
+    fn changed(&self) -> bool {
+        self.shard.get(self.key).is_some_and(|value| self.get_key.get_key(value).borrow() != self.key)
+    }
+}
+
+impl<T, K, F, Q: ?Sized> Drop for KeyCheck<'_, T, K, F, Q> where K: Eq + Hash + Borrow<Q>, Q: Eq + Hash, F: GetKey<T, K> {
+    fn drop(&mut self) {
+        if self.changed() {
+            self.shard.remove(self.key);
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+/// Iterator of `ConcurrentKeySet::snapshot`
+pub struct Snapshot<T> {
+    iter: vec::IntoIter<T>,
+}
+
+impl<T> Iterator for Snapshot<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Snapshot<T> {}
+impl<T> FusedIterator for Snapshot<T> {}
+
+type ShardValues<T, K> = std::iter::FlatMap<
+    vec::IntoIter<HashMap<K, T>>,
+    hash_map::IntoValues<K, T>,
+    fn(HashMap<K, T>) -> hash_map::IntoValues<K, T>
+>;
+
+/// Owning iterator of `ConcurrentKeySet`
+pub struct IntoIter<T, K> {
+    iter: ShardValues<T, K>,
+}
+
+impl<T, K> Iterator for IntoIter<T, K> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+}
+
+impl<T, K> FusedIterator for IntoIter<T, K> {}
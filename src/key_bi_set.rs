@@ -0,0 +1,204 @@
+use std::borrow::{ Borrow };
+use std::collections::{ HashMap };
+use std::error::Error;
+use std::hash::{ Hash };
+use std::iter::{ IntoIterator };
+use std::fmt;
+
+use crate::key_set::{ GetKey, GetKeyType };
+use crate::slab::{ Slab, Iter };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyBiSet
+
+/// A key set with two unique keys, a left and a right one, each with its own
+/// key function.
+///
+/// An element is looked up or removed by either key, an insert clashing with
+/// an element on either side is rejected as a whole.
+pub struct KeyBiSet<T, K1, K2, F1 = GetKeyType<T, K1>, F2 = GetKeyType<T, K2>> {
+    get_left_key: F1,
+    get_right_key: F2,
+    slab: Slab<T>,
+    _left_map: HashMap<K1, usize>,
+    _right_map: HashMap<K2, usize>,
+}
+
+impl<T, K1, K2> KeyBiSet<T, K1, K2> where K1: Eq + Hash, K2: Eq + Hash {
+    pub fn new(get_left_key: GetKeyType<T, K1>, get_right_key: GetKeyType<T, K2>) -> Self {
+        Self::with_get_keys(get_left_key, get_right_key)
+    }
+}
+
+impl<T, K1, K2, F1, F2> KeyBiSet<T, K1, K2, F1, F2>
+where K1: Eq + Hash, K2: Eq + Hash, F1: GetKey<T, K1>, F2: GetKey<T, K2>
+{
+    pub fn with_get_keys(get_left_key: F1, get_right_key: F2) -> Self {
+        KeyBiSet {
+            get_left_key,
+            get_right_key,
+            slab: Slab::new(),
+            _left_map: HashMap::new(),
+            _right_map: HashMap::new(),
+        }
+    }
+
+    /// Insert `value` unless one of its keys is already held.
+    ///
+    /// The error hands the value back with the keys that clashed, the set is left untouched.
+    pub fn insert(&mut self, value: T) -> Result<(), BiCollision<T, K1, K2>> {
+        let left_key = self.get_left_key.get_key(&value);
+        let right_key = self.get_right_key.get_key(&value);
+
+        let left = self._left_map.contains_key(&left_key);
+        let right = self._right_map.contains_key(&right_key);
+
+        if left || right {
+            return Err(BiCollision {
+                left: left.then_some(left_key),
+                right: right.then_some(right_key),
+                value,
+            });
+        }
+
+        let id = self.slab.insert(value);
+
+        self._left_map.insert(left_key, id);
+        self._right_map.insert(right_key, id);
+
+        Ok(())
+    }
+
+    /// Whether an element holds either key of `value`.
+    pub fn collides(&self, value: &T) -> bool {
+        self._left_map.contains_key(&self.get_left_key.get_key(value))
+        || self._right_map.contains_key(&self.get_right_key.get_key(value))
+    }
+
+    pub fn take_by_left<Q>(&mut self, key: &Q) -> Option<T> where K1: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let id = self._left_map.remove(key)?;
+        let value = self.slab.remove(id);
+
+        self._right_map.remove(&self.get_right_key.get_key(&value));
+
+        Some(value)
+    }
+
+    pub fn take_by_right<Q>(&mut self, key: &Q) -> Option<T> where K2: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let id = self._right_map.remove(key)?;
+        let value = self.slab.remove(id);
+
+        self._left_map.remove(&self.get_left_key.get_key(&value));
+
+        Some(value)
+    }
+
+    pub fn remove_by_left<Q>(&mut self, key: &Q) -> bool where K1: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.take_by_left(key).is_some()
+    }
+
+    pub fn remove_by_right<Q>(&mut self, key: &Q) -> bool where K2: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.take_by_right(key).is_some()
+    }
+}
+
+/// Key lookups accept any borrowed form of the keys, e.g. `&str` for a `String` key.
+impl<T, K1, K2, F1, F2> KeyBiSet<T, K1, K2, F1, F2> where K1: Eq + Hash, K2: Eq + Hash {
+    pub fn get_by_left<Q>(&self, key: &Q) -> Option<&T> where K1: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._left_map.get(key).map(|&id| self.slab.get(id))
+    }
+
+    pub fn get_by_right<Q>(&self, key: &Q) -> Option<&T> where K2: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._right_map.get(key).map(|&id| self.slab.get(id))
+    }
+
+    pub fn contains_left<Q>(&self, key: &Q) -> bool where K1: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._left_map.contains_key(key)
+    }
+
+    pub fn contains_right<Q>(&self, key: &Q) -> bool where K2: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._right_map.contains_key(key)
+    }
+}
+
+impl<T, K1, K2, F1, F2> KeyBiSet<T, K1, K2, F1, F2> {
+    pub fn len(&self) -> usize {
+        self.slab.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.slab.iter()
+    }
+}
+
+impl<'a, T, K1, K2, F1, F2> IntoIterator for &'a KeyBiSet<T, K1, K2, F1, F2> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Debug for KeyBiSet
+impl<T, K1, K2, F1, F2> fmt::Debug for KeyBiSet<T, K1, K2, F1, F2> where T: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Errors
+
+/// A value rejected by `KeyBiSet::insert`, with the keys of it already held.
+///
+/// The elements holding them are found with `get_by_left` and `get_by_right`.
+pub struct BiCollision<T, K1, K2> {
+    left: Option<K1>,
+    right: Option<K2>,
+    value: T,
+}
+
+impl<T, K1, K2> BiCollision<T, K1, K2> {
+    /// The left key of the value, if an element already holds it.
+    pub fn left_key(&self) -> Option<&K1> {
+        self.left.as_ref()
+    }
+
+    /// The right key of the value, if an element already holds it.
+    pub fn right_key(&self) -> Option<&K2> {
+        self.right.as_ref()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, K1, K2> fmt::Debug for BiCollision<T, K1, K2> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BiCollision")
+         .field("left", &self.left.is_some())
+         .field("right", &self.right.is_some())
+         .finish_non_exhaustive()
+    }
+}
+
+impl<T, K1, K2> fmt::Display for BiCollision<T, K1, K2> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.left.is_some(), self.right.is_some()) {
+            (true, true) => write!(f, "both keys of the element are already held"),
+            (true, false) => write!(f, "the left key of the element is already held"),
+            _ => write!(f, "the right key of the element is already held"),
+        }
+    }
+}
+
+impl<T, K1, K2> Error for BiCollision<T, K1, K2> {}
+
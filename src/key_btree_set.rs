@@ -0,0 +1,601 @@
+use std::borrow::{ Borrow };
+use std::cmp::{ Ordering };
+use std::collections::{ BTreeMap };
+use std::collections::btree_map;
+use std::iter::{ FromIterator, FusedIterator, IntoIterator, Peekable };
+use std::marker::{ PhantomData };
+use std::ops::{ RangeBounds };
+use std::vec;
+use std::mem;
+use std::fmt;
+
+use crate::key_set::{
+    KeySet, GetKey, GetKeyType, DuplicatePolicy, InsertOutcome, Bias, assert_same_key
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyBTreeSet
+
+/// A key set iterating in key order, backed by `BTreeMap`.
+///
+/// The set operations walk both sides once in key order, so they're linear
+/// instead of a lookup per element.
+pub struct KeyBTreeSet<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    duplicate_policy: DuplicatePolicy<T>,
+    _value_map: BTreeMap<K, T>,
+}
+
+impl<T, K> KeyBTreeSet<T, K> where K: Ord {
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+
+    pub fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
+        Self::from_intoiter_with(get_key, iter)
+    }
+}
+
+impl<T, K, F> KeyBTreeSet<T, K, F> where K: Ord, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F) -> Self {
+        KeyBTreeSet {
+            get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            _value_map: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.insert_all(iter);
+        this
+    }
+
+    pub fn from_intoiter_with_policy(
+        get_key: F,
+        policy: DuplicatePolicy<T>,
+        iter: impl IntoIterator<Item=T>
+    ) -> Self
+    {
+        let mut this = Self::with_get_key(get_key);
+        this.duplicate_policy = policy;
+        this.insert_all(iter);
+        this
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy<T> {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy<T>) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Split the set at `key`, returning the elements with a key `>= key`.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self where K: Borrow<Q>, Q: ?Sized + Ord, F: Clone {
+        let mut new_set = self.empty_like();
+        new_set._value_map = self._value_map.split_off(key);
+
+        new_set
+    }
+
+    /// Empty set with the same key function and policy.
+    fn empty_like(&self) -> Self where F: Clone {
+        KeyBTreeSet {
+            get_key: self.get_key.clone(),
+            duplicate_policy: self.duplicate_policy,
+            _value_map: BTreeMap::new(),
+        }
+    }
+
+    /// Set with the same key function and policy over entries already sorted by key.
+    fn sorted_like(&self, entries: Vec<(K, T)>) -> Self where F: Clone {
+        let mut new_set = self.empty_like();
+        // `BTreeMap::from_iter` bulk loads, its stable sort is linear on sorted input
+        new_set._value_map = BTreeMap::from_iter(entries);
+
+        new_set
+    }
+
+    fn insert_value(&mut self, value: T) -> InsertOutcome<T> {
+        let key = self.get_key.get_key(&value);
+
+        match self._value_map.entry(key) {
+            btree_map::Entry::Occupied(mut entry) => {
+                let outcome = self.duplicate_policy.resolve(entry.get_mut(), value);
+
+                if let InsertOutcome::Merged = outcome {
+                    assert_same_key(&self.get_key, entry.key(), entry.get());
+                }
+                outcome
+            },
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                InsertOutcome::Inserted
+            },
+        }
+    }
+
+    /// Bulk insert, the elements refused under `DuplicatePolicy::Reject` are handed back.
+    fn insert_all(&mut self, iter: impl IntoIterator<Item=T>) -> Vec<T> {
+        let mut rejected = Vec::new();
+
+        for value in iter {
+            if let InsertOutcome::Rejected(value) = self.insert_value(value) {
+                rejected.push(value);
+            }
+        }
+
+        rejected
+    }
+
+    /// Like `extend`, but the elements refused under `DuplicatePolicy::Reject`
+    /// are handed back, in iteration order, instead of dropped.
+    pub fn try_extend(&mut self, iter: impl IntoIterator<Item=T>) -> Result<(), Vec<T>> {
+        let rejected = self.insert_all(iter);
+
+        if rejected.is_empty() { Ok(()) } else { Err(rejected) }
+    }
+
+    /// The element kept under `bias` for a key held by both sides.
+    fn pick(&self, key: &K, left: &T, right: &T, bias: Bias<T>) -> T where T: Clone {
+        match bias {
+            Bias::Left => left.clone(),
+            Bias::Right => right.clone(),
+            Bias::Merge(merge) => {
+                let merged = merge(left, right);
+                assert_same_key(&self.get_key, key, &merged);
+
+                merged
+            },
+        }
+    }
+
+    fn keyed(&self, value: T) -> (K, T) {
+        (self.get_key.get_key(&value), value)
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> KeyBTreeSet<T, K, F> where K: Ord {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Ord {
+        self._value_map.contains_key(key)
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Ord {
+        self._value_map.get(key)
+    }
+
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Ord {
+        self._value_map.remove(key).is_some()
+    }
+
+    pub fn take_by_key<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Ord {
+        self._value_map.remove(key)
+    }
+
+    /// Elements with a key in `range`, in key order.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, T, K> where K: Borrow<Q>, Q: ?Sized + Ord, R: RangeBounds<Q> {
+        Range {
+            iter: self._value_map.range(range),
+        }
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self._value_map.first_key_value().map(|(_, v)| v)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self._value_map.last_key_value().map(|(_, v)| v)
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self._value_map.pop_first().map(|(_, v)| v)
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self._value_map.pop_last().map(|(_, v)| v)
+    }
+
+    pub fn keys(&self) -> btree_map::Keys<'_, K, T> {
+        self._value_map.keys()
+    }
+
+    pub fn iter_with_keys(&self) -> btree_map::Iter<'_, K, T> {
+        self._value_map.iter()
+    }
+}
+
+impl<T, K, F> KeySet<T, K> for KeyBTreeSet<T, K, F> where K: Ord, F: GetKey<T, K> {
+    type KeyFn = F;
+    type Iter<'a> = Iter<'a, T, K> where Self: 'a, T: 'a;
+    type Drain<'a> = Drain<'a, T, K> where Self: 'a, T: 'a;
+
+    fn with_get_key(get_key: F) -> Self {
+        KeyBTreeSet::with_get_key(get_key)
+    }
+
+    fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        KeyBTreeSet::from_intoiter_with(get_key, iter)
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        self.insert_value(value)
+    }
+
+    fn try_insert(&mut self, value: T) -> Result<&T, T> {
+        let key = self.get_key.get_key(&value);
+
+        match self._value_map.entry(key) {
+            btree_map::Entry::Occupied(_) => Err(value),
+            btree_map::Entry::Vacant(entry) => Ok(entry.insert(value)),
+        }
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self._value_map.contains_key(&self.get_key.get_key(value))
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        self._value_map.remove(&self.get_key.get_key(value)).is_some()
+    }
+
+    fn take(&mut self, value: &T) -> Option<T> {
+        self._value_map.remove(&self.get_key.get_key(value))
+    }
+
+    fn get(&self, value: &T) -> Option<&T> {
+        self._value_map.get(&self.get_key.get_key(value))
+    }
+
+    fn len(&self) -> usize {
+        self._value_map.len()
+    }
+
+    fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            iter: self._value_map.values(),
+        }
+    }
+
+    fn retain<P: FnMut(&T) -> bool>(&mut self, mut pred: P) {
+        self._value_map.retain(|_, v| pred(v));
+    }
+
+    fn drain(&mut self) -> Drain<'_, T, K> {
+        Drain {
+            iter: mem::take(&mut self._value_map).into_values(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn extract_if<'a, P: FnMut(&T) -> bool + 'a>(&'a mut self, pred: P) -> impl Iterator<Item=T> + 'a {
+        let keys: Vec<K> = self._value_map.values().map(|v| self.get_key.get_key(v)).collect();
+
+        ExtractIf {
+            map: &mut self._value_map,
+            keys: keys.into_iter(),
+            pred,
+        }
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        KeyBTreeSet::contains_key(self, key)
+    }
+
+    fn get_by_key(&self, key: &K) -> Option<&T> {
+        KeyBTreeSet::get_by_key(self, key)
+    }
+
+    fn remove_by_key(&mut self, key: &K) -> bool {
+        KeyBTreeSet::remove_by_key(self, key)
+    }
+
+    fn take_by_key(&mut self, key: &K) -> Option<T> {
+        KeyBTreeSet::take_by_key(self, key)
+    }
+
+    fn intersection_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self._value_map.iter(), other._value_map.iter())
+            .filter_map(|side| match side {
+                Side::Both((key, v), (_, other_v)) => Some(self.keyed(self.pick(key, v, other_v, bias))),
+                _ => None,
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn union_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self._value_map.iter(), other._value_map.iter())
+            .map(|side| match side {
+                Side::Left((_, v)) | Side::Right((_, v)) => self.keyed(v.clone()),
+                Side::Both((key, v), (_, other_v)) => self.keyed(self.pick(key, v, other_v, bias)),
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self._value_map.iter(), other._value_map.iter())
+            .filter_map(|side| match side {
+                Side::Left((_, v)) => Some(self.keyed(v.clone())),
+                _ => None,
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self._value_map.iter(), other._value_map.iter())
+            .filter_map(|side| match side {
+                Side::Left((_, v)) | Side::Right((_, v)) => Some(self.keyed(v.clone())),
+                Side::Both(..) => None,
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn union_with(&mut self, other: Self) {
+        let map = mem::take(&mut self._value_map);
+
+        self._value_map = merge_join::<K, _, _>(map.into_iter(), other._value_map.into_iter())
+            .map(|side| match side {
+                Side::Left(entry) | Side::Right(entry) | Side::Both(entry, _) => entry,
+            })
+            .collect();
+    }
+
+    fn retain_intersection(&mut self, other: &Self) {
+        let map = mem::take(&mut self._value_map);
+
+        self._value_map = merge_join::<K, _, _>(map.into_iter(), other._value_map.iter())
+            .filter_map(|side| match side {
+                Side::Both(entry, _) => Some(entry),
+                _ => None,
+            })
+            .collect();
+    }
+
+    fn subtract(&mut self, other: &Self) {
+        let map = mem::take(&mut self._value_map);
+
+        self._value_map = merge_join::<K, _, _>(map.into_iter(), other._value_map.iter())
+            .filter_map(|side| match side {
+                Side::Left(entry) => Some(entry),
+                _ => None,
+            })
+            .collect();
+    }
+
+    fn symmetric_difference_with(&mut self, other: Self) {
+        let map = mem::take(&mut self._value_map);
+
+        self._value_map = merge_join::<K, _, _>(map.into_iter(), other._value_map.into_iter())
+            .filter_map(|side| match side {
+                Side::Left(entry) | Side::Right(entry) => Some(entry),
+                Side::Both(..) => None,
+            })
+            .collect();
+    }
+}
+
+/// IntoIterator for KeyBTreeSet
+impl<T, K, F> IntoIterator for KeyBTreeSet<T, K, F> {
+    type Item = T;
+    type IntoIter = btree_map::IntoValues<K, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self._value_map.into_values()
+    }
+}
+
+impl<'a, T, K, F> IntoIterator for &'a KeyBTreeSet<T, K, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Iter<'a, T, K> {
+        Iter {
+            iter: self._value_map.values(),
+        }
+    }
+}
+
+/// PartialEq for KeyBTreeSet
+impl<T, K, F> PartialEq for KeyBTreeSet<T, K, F> where K: Ord {
+    fn eq(&self, other: &Self) -> bool {
+        self._value_map.len() == other._value_map.len()
+        && self._value_map.keys().eq(other._value_map.keys())
+    }
+}
+
+/// Debug for KeyBTreeSet
+impl<T, K, F> fmt::Debug for KeyBTreeSet<T, K, F> where T: fmt::Debug, K: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyBTreeSet")
+         .field("_value_map", &self._value_map)
+         .finish()
+    }
+}
+
+/// Extend for KeyBTreeSet, elements refused under `DuplicatePolicy::Reject` are dropped
+impl<T, K, F> Extend<T> for KeyBTreeSet<T, K, F> where K: Ord, F: GetKey<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.insert_all(iter);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Merge join
+
+/// Which side of a merge join holds a key.
+pub(crate) enum Side<L, R> {
+    Left(L),
+    Right(R),
+    Both(L, R),
+}
+
+/// Walk two iterators of entries sorted by key in step.
+pub(crate) struct MergeJoin<K: ?Sized, I: Iterator, J: Iterator> {
+    left: Peekable<I>,
+    right: Peekable<J>,
+    _key: PhantomData<fn(&K)>,
+}
+
+pub(crate) fn merge_join<K, I, J>(left: I, right: J) -> MergeJoin<K, I, J> where K: ?Sized, I: Iterator, J: Iterator {
+    MergeJoin {
+        left: left.peekable(),
+        right: right.peekable(),
+        _key: PhantomData,
+    }
+}
+
+impl<K, KL, KR, L, R, I, J> Iterator for MergeJoin<K, I, J>
+where
+    K: ?Sized + Ord,
+    KL: Borrow<K>,
+    KR: Borrow<K>,
+    I: Iterator<Item=(KL, L)>,
+    J: Iterator<Item=(KR, R)>,
+{
+    type Item = Side<(KL, L), (KR, R)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ord = match (self.left.peek(), self.right.peek()) {
+            (Some((l, _)), Some((r, _))) => l.borrow().cmp(r.borrow()),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+
+        Some(match ord {
+            Ordering::Less => Side::Left(self.left.next()?),
+            Ordering::Greater => Side::Right(self.right.next()?),
+            Ordering::Equal => Side::Both(self.left.next()?, self.right.next()?),
+        })
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+pub struct Iter<'a, T, K> {
+    iter: btree_map::Values<'a, K, T>,
+}
+
+impl<'a, T, K> Iterator for Iter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> DoubleEndedIterator for Iter<'_, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Iter<'_, T, K> {}
+impl<T, K> FusedIterator for Iter<'_, T, K> {}
+
+impl<T, K> Clone for Iter<'_, T, K> {
+    fn clone(&self) -> Self {
+        Iter { iter: self.iter.clone() }
+    }
+}
+
+/// Iterator of `KeyBTreeSet::range`
+pub struct Range<'a, T, K> {
+    iter: btree_map::Range<'a, K, T>,
+}
+
+impl<'a, T, K> Iterator for Range<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> DoubleEndedIterator for Range<'_, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<T, K> FusedIterator for Range<'_, T, K> {}
+
+impl<T, K> Clone for Range<'_, T, K> {
+    fn clone(&self) -> Self {
+        Range { iter: self.iter.clone() }
+    }
+}
+
+/// Draining iterator of `KeyBTreeSet`, in key order.
+///
+/// The set is emptied up front, the elements not yet yielded are dropped with it.
+pub struct Drain<'a, T, K> {
+    iter: btree_map::IntoValues<K, T>,
+    _marker: PhantomData<&'a mut BTreeMap<K, T>>,
+}
+
+impl<T, K> Iterator for Drain<'_, T, K> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Drain<'_, T, K> {}
+impl<T, K> FusedIterator for Drain<'_, T, K> {}
+
+/// Iterator of `KeyBTreeSet::extract_if`, in key order.
+///
+/// The keys are taken up front and each element looked up as it is visited,
+/// the unvisited ones stay in the set when the iterator is dropped or leaked.
+pub struct ExtractIf<'a, T, K, P> {
+    map: &'a mut BTreeMap<K, T>,
+    keys: vec::IntoIter<K>,
+    pred: P,
+}
+
+impl<T, K, P> Iterator for ExtractIf<'_, T, K, P> where K: Ord, P: FnMut(&T) -> bool {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        for key in self.keys.by_ref() {
+            if self.map.get(&key).is_some_and(&mut self.pred) {
+                return self.map.remove(&key);
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.keys.len()))
+    }
+}
+
+impl<T, K, P> FusedIterator for ExtractIf<'_, T, K, P> where K: Ord, P: FnMut(&T) -> bool {}
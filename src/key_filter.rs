@@ -0,0 +1,439 @@
+use std::borrow::{ Borrow };
+use std::collections::hash_map::{ DefaultHasher };
+use std::f64::consts::{ LN_2 };
+use std::hash::{ Hash, Hasher };
+use std::marker::{ PhantomData };
+use std::mem;
+use std::fmt;
+
+use crate::key_set::{ GetKey, GetKeyType, KeyHashSet };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Hashing
+
+/// Keys are hashed with unkeyed SipHash so that two filters of the same shape
+/// agree on the bits of a key and can be merged. The hash isn't stable across
+/// Rust releases, a filter isn't meant to be persisted.
+fn hash_key<Q>(key: &Q) -> u64 where Q: ?Sized + Hash {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// splitmix64 finalizer, derives a second independent-looking hash.
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    x ^ (x >> 31)
+}
+
+fn assert_fp_rate(fp_rate: f64) {
+    assert!(fp_rate > 0.0 && fp_rate < 1.0, "the false positive rate must be within (0, 1)");
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyBloomFilter
+
+/// A Bloom filter over the keys of the elements, answering "definitely not
+/// present" or "maybe present" without holding the elements.
+///
+/// Keys can't be removed, see `KeyCuckooFilter` for that.
+pub struct KeyBloomFilter<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    bits: Vec<u64>,
+    hash_count: u32,
+    _marker: PhantomData<fn(&T) -> K>,
+}
+
+impl<T, K> KeyBloomFilter<T, K> where K: Hash {
+    pub fn new(get_key: GetKeyType<T, K>, expected: usize, fp_rate: f64) -> Self {
+        Self::with_get_key(get_key, expected, fp_rate)
+    }
+}
+
+impl<T, K, F> KeyBloomFilter<T, K, F> where K: Hash, F: GetKey<T, K> {
+    /// Filter sized to stay under `fp_rate` false positives with `expected` keys.
+    ///
+    /// Panics unless `fp_rate` is within (0, 1).
+    pub fn with_get_key(get_key: F, expected: usize, fp_rate: f64) -> Self {
+        assert_fp_rate(fp_rate);
+
+        let expected = expected.max(1) as f64;
+        let bit_len = (-expected * fp_rate.ln() / (LN_2 * LN_2)).ceil() as usize;
+        let words = bit_len.div_ceil(64).max(1);
+        let hash_count = ((words * 64) as f64 / expected * LN_2).round().clamp(1.0, 32.0) as u32;
+
+        KeyBloomFilter {
+            get_key,
+            bits: vec![0; words],
+            hash_count,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Filter of the keys of `set`, sized for its length.
+    pub fn from_key_hash_set<S>(set: &KeyHashSet<T, K, F, S>, fp_rate: f64) -> Self where F: Clone {
+        let mut this = Self::with_get_key(set.get_key_fn().clone(), set.keys().len(), fp_rate);
+
+        for key in set.keys() {
+            this.insert_hash(hash_key(key));
+        }
+        this
+    }
+
+    /// Add the key of `value`, returns false if it might have been there already.
+    pub fn insert(&mut self, value: &T) -> bool {
+        self.insert_hash(hash_key(&self.get_key.get_key(value)))
+    }
+
+    pub fn might_contain(&self, value: &T) -> bool {
+        self.might_contain_key(&self.get_key.get_key(value))
+    }
+
+    /// Filter holding the keys of both filters, see `union_with`.
+    pub fn union(&self, other: &Self) -> Self where F: Clone {
+        let mut this = self.clone();
+        this.union_with(other);
+
+        this
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> KeyBloomFilter<T, K, F> where K: Hash {
+    pub fn might_contain_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Hash {
+        let (h1, h2) = Self::split_hash(hash_key(key));
+
+        (0..self.hash_count).all(|i| self.get_bit(self.position(h1, h2, i)))
+    }
+}
+
+impl<T, K, F> KeyBloomFilter<T, K, F> {
+    /// Add the keys of `other`.
+    ///
+    /// Panics unless both filters were sized alike.
+    pub fn union_with(&mut self, other: &Self) {
+        assert!(
+            self.bits.len() == other.bits.len() && self.hash_count == other.hash_count,
+            "only filters sized alike can be merged"
+        );
+
+        for (word, other) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *word |= other;
+        }
+    }
+
+    pub fn bit_len(&self) -> usize {
+        self.bits.len() * 64
+    }
+
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// False positive rate for the current fill, `(set bits / bits) ^ hashes`.
+    pub fn estimated_fp_rate(&self) -> f64 {
+        let ones: u32 = self.bits.iter().map(|word| word.count_ones()).sum();
+
+        (ones as f64 / self.bit_len() as f64).powi(self.hash_count as i32)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|word| *word = 0);
+    }
+
+    fn insert_hash(&mut self, hash: u64) -> bool {
+        let (h1, h2) = Self::split_hash(hash);
+        let mut fresh = false;
+
+        for i in 0..self.hash_count {
+            let pos = self.position(h1, h2, i);
+
+            fresh |= !self.get_bit(pos);
+            self.bits[pos / 64] |= 1 << (pos % 64);
+        }
+        fresh
+    }
+
+    /// Double hashing, the i-th probe is `h1 + i * h2`.
+    fn split_hash(hash: u64) -> (u64, u64) {
+        (hash, mix(hash) | 1)
+    }
+
+    fn position(&self, h1: u64, h2: u64, i: u32) -> usize {
+        (h1.wrapping_add(h2.wrapping_mul(i as u64)) % self.bit_len() as u64) as usize
+    }
+
+    fn get_bit(&self, pos: usize) -> bool {
+        self.bits[pos / 64] & (1 << (pos % 64)) != 0
+    }
+}
+
+/// Clone for KeyBloomFilter
+impl<T, K, F> Clone for KeyBloomFilter<T, K, F> where F: Clone {
+    fn clone(&self) -> Self {
+        KeyBloomFilter {
+            get_key: self.get_key.clone(),
+            bits: self.bits.clone(),
+            hash_count: self.hash_count,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Debug for KeyBloomFilter
+impl<T, K, F> fmt::Debug for KeyBloomFilter<T, K, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyBloomFilter")
+         .field("bit_len", &self.bit_len())
+         .field("hash_count", &self.hash_count)
+         .finish_non_exhaustive()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyCuckooFilter
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+
+/// A cuckoo filter over the keys of the elements, like `KeyBloomFilter` but
+/// keys can be removed again.
+///
+/// It stores a fingerprint per insert, so a key inserted twice is removed
+/// twice, and removing a key that was never inserted may evict another key
+/// with the same fingerprint.
+///
+/// When a relocation fails the homeless fingerprint is kept aside and the
+/// filter is full: `insert` returns false until a removal makes room.
+pub struct KeyCuckooFilter<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    /// Fingerprints, zero marks an empty slot.
+    buckets: Vec<[u16; BUCKET_SIZE]>,
+    fingerprint_mask: u16,
+    len: usize,
+    /// The fingerprint left homeless by the last failed relocation.
+    victim: Option<(usize, u16)>,
+    _marker: PhantomData<fn(&T) -> K>,
+}
+
+impl<T, K> KeyCuckooFilter<T, K> where K: Hash {
+    pub fn new(get_key: GetKeyType<T, K>, expected: usize, fp_rate: f64) -> Self {
+        Self::with_get_key(get_key, expected, fp_rate)
+    }
+}
+
+impl<T, K, F> KeyCuckooFilter<T, K, F> where K: Hash, F: GetKey<T, K> {
+    /// Filter with room for `expected` keys at a load of 95%, with fingerprints
+    /// wide enough for `fp_rate` false positives, 16 bits at most.
+    ///
+    /// Panics unless `fp_rate` is within (0, 1).
+    pub fn with_get_key(get_key: F, expected: usize, fp_rate: f64) -> Self {
+        assert_fp_rate(fp_rate);
+
+        let fingerprint_bits = (2.0 * BUCKET_SIZE as f64 / fp_rate).log2().ceil().clamp(4.0, 16.0) as u32;
+        let bucket_count = (expected.max(1) as f64 / (BUCKET_SIZE as f64 * 0.95)).ceil() as usize;
+
+        KeyCuckooFilter {
+            get_key,
+            buckets: vec![[0; BUCKET_SIZE]; bucket_count.next_power_of_two()],
+            fingerprint_mask: (u32::MAX >> (32 - fingerprint_bits)) as u16,
+            len: 0,
+            victim: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Filter of the keys of `set`, sized for its length.
+    pub fn from_key_hash_set<S>(set: &KeyHashSet<T, K, F, S>, fp_rate: f64) -> Self where F: Clone {
+        let mut this = Self::with_get_key(set.get_key_fn().clone(), set.keys().len(), fp_rate);
+
+        for key in set.keys() {
+            let (index, fingerprint) = this.locate(hash_key(key));
+            this.insert_fingerprint(index, fingerprint);
+        }
+        this
+    }
+
+    /// Add the key of `value`, returns false if the filter is full.
+    pub fn insert(&mut self, value: &T) -> bool {
+        let (index, fingerprint) = self.locate(hash_key(&self.get_key.get_key(value)));
+
+        self.insert_fingerprint(index, fingerprint)
+    }
+
+    pub fn might_contain(&self, value: &T) -> bool {
+        self.might_contain_key(&self.get_key.get_key(value))
+    }
+
+    /// Remove the key of `value` once, returns false if it wasn't there.
+    pub fn remove(&mut self, value: &T) -> bool {
+        self.remove_key(&self.get_key.get_key(value))
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> KeyCuckooFilter<T, K, F> where K: Hash {
+    pub fn might_contain_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Hash {
+        let (i1, fingerprint) = self.locate(hash_key(key));
+        let i2 = self.alt_index(i1, fingerprint);
+
+        self.buckets[i1].contains(&fingerprint)
+        || self.buckets[i2].contains(&fingerprint)
+        || self.victim.is_some_and(|(index, victim)| victim == fingerprint && (index == i1 || index == i2))
+    }
+
+    pub fn remove_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Hash {
+        let (i1, fingerprint) = self.locate(hash_key(key));
+        let i2 = self.alt_index(i1, fingerprint);
+
+        if let Some((index, victim)) = self.victim {
+            if victim == fingerprint && (index == i1 || index == i2) {
+                self.victim = None;
+                self.len -= 1;
+                return true;
+            }
+        }
+
+        for index in [i1, i2] {
+            if let Some(slot) = self.buckets[index].iter_mut().find(|slot| **slot == fingerprint) {
+                *slot = 0;
+                self.len -= 1;
+
+                // there is room for the homeless fingerprint now
+                if let Some((index, victim)) = self.victim.take() {
+                    self.len -= 1;
+                    self.insert_fingerprint(index, victim);
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl<T, K, F> KeyCuckooFilter<T, K, F> {
+    /// Add the keys of `other`, returns false if the filter got full on the way,
+    /// some of the keys are missing then.
+    ///
+    /// Panics unless both filters were sized alike.
+    pub fn union_with(&mut self, other: &Self) -> bool {
+        assert!(
+            self.buckets.len() == other.buckets.len() && self.fingerprint_mask == other.fingerprint_mask,
+            "only filters sized alike can be merged"
+        );
+
+        let fingerprints = other.buckets
+            .iter()
+            .enumerate()
+            .flat_map(|(index, bucket)| bucket.iter().map(move |&fingerprint| (index, fingerprint)))
+            .chain(other.victim);
+
+        for (index, fingerprint) in fingerprints {
+            if fingerprint != 0 && !self.insert_fingerprint(index, fingerprint) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Number of fingerprints held.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of fingerprint slots.
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|bucket| *bucket = [0; BUCKET_SIZE]);
+        self.victim = None;
+        self.len = 0;
+    }
+
+    /// Primary bucket and nonzero fingerprint of a key hash.
+    fn locate(&self, hash: u64) -> (usize, u16) {
+        let fingerprint = match (hash >> 48) as u16 & self.fingerprint_mask {
+            0 => 1,
+            fingerprint => fingerprint,
+        };
+
+        (hash as usize & (self.buckets.len() - 1), fingerprint)
+    }
+
+    /// The other bucket of `fingerprint`, an involution as the count is a power of two.
+    fn alt_index(&self, index: usize, fingerprint: u16) -> usize {
+        (index ^ mix(fingerprint as u64) as usize) & (self.buckets.len() - 1)
+    }
+
+    fn insert_fingerprint(&mut self, index: usize, fingerprint: u16) -> bool {
+        if self.victim.is_some() {
+            return false;
+        }
+
+        let mut index = index;
+        let mut fingerprint = fingerprint;
+
+        for kick in 0..MAX_KICKS {
+            for index in [index, self.alt_index(index, fingerprint)] {
+                if let Some(slot) = self.buckets[index].iter_mut().find(|slot| **slot == 0) {
+                    *slot = fingerprint;
+                    self.len += 1;
+                    return true;
+                }
+            }
+
+            // both buckets full, evict a fingerprint and move it to its other bucket
+            let slot = mix(fingerprint as u64 ^ kick as u64) as usize % BUCKET_SIZE;
+            mem::swap(&mut fingerprint, &mut self.buckets[index][slot]);
+            index = self.alt_index(index, fingerprint);
+        }
+
+        // the last evicted one is kept aside, nothing is lost
+        self.victim = Some((index, fingerprint));
+        self.len += 1;
+
+        true
+    }
+}
+
+/// Clone for KeyCuckooFilter
+impl<T, K, F> Clone for KeyCuckooFilter<T, K, F> where F: Clone {
+    fn clone(&self) -> Self {
+        KeyCuckooFilter {
+            get_key: self.get_key.clone(),
+            buckets: self.buckets.clone(),
+            fingerprint_mask: self.fingerprint_mask,
+            len: self.len,
+            victim: self.victim,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Debug for KeyCuckooFilter
+impl<T, K, F> fmt::Debug for KeyCuckooFilter<T, K, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyCuckooFilter")
+         .field("len", &self.len)
+         .field("capacity", &self.capacity())
+         .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,541 @@
+use std::borrow::{ Borrow };
+use std::cmp::{ Ordering };
+use std::hash::{ Hash };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::ops::{ Index };
+use std::fmt;
+
+use indexmap::{ IndexMap };
+use indexmap::map;
+
+use crate::key_set::{
+    KeySet, GetKey, GetKeyType, DuplicatePolicy, InsertOutcome, Bias, assert_same_key
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyIndexSet
+
+/// A key set iterating in insertion order, backed by `IndexMap`.
+///
+/// Replacing an element keeps its position, `remove` and `take` shift the
+/// following elements to keep the order, the `swap_` methods trade it for O(1).
+pub struct KeyIndexSet<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    duplicate_policy: DuplicatePolicy<T>,
+    _value_map: IndexMap<K, T>,
+}
+
+impl<T, K> KeyIndexSet<T, K> where K: Eq + Hash {
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+
+    pub fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
+        Self::from_intoiter_with(get_key, iter)
+    }
+}
+
+impl<T, K, F> KeyIndexSet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F) -> Self {
+        KeyIndexSet {
+            get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            _value_map: IndexMap::new(),
+        }
+    }
+
+    pub fn with_capacity(get_key: F, capacity: usize) -> Self {
+        KeyIndexSet {
+            get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            _value_map: IndexMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.insert_all(iter);
+        this
+    }
+
+    pub fn from_intoiter_with_policy(
+        get_key: F,
+        policy: DuplicatePolicy<T>,
+        iter: impl IntoIterator<Item=T>
+    ) -> Self
+    {
+        let mut this = Self::with_get_key(get_key);
+        this.duplicate_policy = policy;
+        this.insert_all(iter);
+        this
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy<T> {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy<T>) {
+        self.duplicate_policy = policy;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self._value_map.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self._value_map.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self._value_map.shrink_to_fit();
+    }
+
+    /// Empty set with the same key function and policy.
+    fn empty_like(&self) -> Self where F: Clone {
+        KeyIndexSet {
+            get_key: self.get_key.clone(),
+            duplicate_policy: self.duplicate_policy,
+            _value_map: IndexMap::new(),
+        }
+    }
+
+    fn insert_value(&mut self, value: T) -> InsertOutcome<T> {
+        let key = self.get_key.get_key(&value);
+
+        match self._value_map.entry(key) {
+            map::Entry::Occupied(mut entry) => {
+                let outcome = self.duplicate_policy.resolve(entry.get_mut(), value);
+
+                if let InsertOutcome::Merged = outcome {
+                    assert_same_key(&self.get_key, entry.key(), entry.get());
+                }
+                outcome
+            },
+            map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                InsertOutcome::Inserted
+            },
+        }
+    }
+
+    /// Bulk insert, the elements refused under `DuplicatePolicy::Reject` are handed back.
+    fn insert_all(&mut self, iter: impl IntoIterator<Item=T>) -> Vec<T> {
+        let iter = iter.into_iter();
+
+        let hint = iter.size_hint().0;
+        self._value_map.reserve(if self._value_map.is_empty() { hint } else { hint.div_ceil(2) });
+
+        let mut rejected = Vec::new();
+
+        for value in iter {
+            if let InsertOutcome::Rejected(value) = self.insert_value(value) {
+                rejected.push(value);
+            }
+        }
+
+        rejected
+    }
+
+    /// Like `extend`, but the elements refused under `DuplicatePolicy::Reject`
+    /// are handed back, in iteration order, instead of dropped.
+    pub fn try_extend(&mut self, iter: impl IntoIterator<Item=T>) -> Result<(), Vec<T>> {
+        let rejected = self.insert_all(iter);
+
+        if rejected.is_empty() { Ok(()) } else { Err(rejected) }
+    }
+
+    /// The element kept under `bias` for a key held by both sides.
+    fn pick(&self, key: &K, left: &T, right: &T, bias: Bias<T>) -> T where T: Clone {
+        match bias {
+            Bias::Left => left.clone(),
+            Bias::Right => right.clone(),
+            Bias::Merge(merge) => {
+                let merged = merge(left, right);
+                assert_same_key(&self.get_key, key, &merged);
+
+                merged
+            },
+        }
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> KeyIndexSet<T, K, F> where K: Eq + Hash {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.contains_key(key)
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.get(key)
+    }
+
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.shift_remove(key).is_some()
+    }
+
+    pub fn take_by_key<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.shift_remove(key)
+    }
+
+    /// Remove by key, moving the last element into its place.
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.swap_remove(key)
+    }
+
+    /// Remove by key, shifting the following elements down.
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.shift_remove(key)
+    }
+
+    pub fn get_index_of<Q>(&self, key: &Q) -> Option<usize> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.get_index_of(key)
+    }
+
+    /// Stable sort of the elements.
+    pub fn sort_by<C>(&mut self, mut cmp: C) where C: FnMut(&T, &T) -> Ordering {
+        self._value_map.sort_by(|_, a, _, b| cmp(a, b));
+    }
+
+    /// Stable sort of the elements by `f`, which may be any ordering, not only the key.
+    pub fn sort_by_key<O, G>(&mut self, mut f: G) where O: Ord, G: FnMut(&T) -> O {
+        self._value_map.sort_by(|_, a, _, b| f(a).cmp(&f(b)));
+    }
+}
+
+/// Index based access, in insertion order unless sorted.
+impl<T, K, F> KeyIndexSet<T, K, F> {
+    pub fn get_index(&self, index: usize) -> Option<&T> {
+        self._value_map.get_index(index).map(|(_, v)| v)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self._value_map.first().map(|(_, v)| v)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self._value_map.last().map(|(_, v)| v)
+    }
+
+    pub fn swap_remove_index(&mut self, index: usize) -> Option<T> {
+        self._value_map.swap_remove_index(index).map(|(_, v)| v)
+    }
+
+    pub fn shift_remove_index(&mut self, index: usize) -> Option<T> {
+        self._value_map.shift_remove_index(index).map(|(_, v)| v)
+    }
+
+    pub fn keys(&self) -> map::Keys<'_, K, T> {
+        self._value_map.keys()
+    }
+
+    pub fn iter_with_keys(&self) -> map::Iter<'_, K, T> {
+        self._value_map.iter()
+    }
+}
+
+impl<T, K, F> KeySet<T, K> for KeyIndexSet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    type KeyFn = F;
+    type Iter<'a> = Iter<'a, T, K> where Self: 'a, T: 'a;
+    type Drain<'a> = Drain<'a, T, K> where Self: 'a, T: 'a;
+
+    fn with_get_key(get_key: F) -> Self {
+        KeyIndexSet::with_get_key(get_key)
+    }
+
+    fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        KeyIndexSet::from_intoiter_with(get_key, iter)
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        self.insert_value(value)
+    }
+
+    fn try_insert(&mut self, value: T) -> Result<&T, T> {
+        let key = self.get_key.get_key(&value);
+
+        match self._value_map.entry(key) {
+            map::Entry::Occupied(_) => Err(value),
+            map::Entry::Vacant(entry) => Ok(entry.insert(value)),
+        }
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self._value_map.contains_key(&self.get_key.get_key(value))
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        self._value_map.shift_remove(&self.get_key.get_key(value)).is_some()
+    }
+
+    fn take(&mut self, value: &T) -> Option<T> {
+        self._value_map.shift_remove(&self.get_key.get_key(value))
+    }
+
+    fn get(&self, value: &T) -> Option<&T> {
+        self._value_map.get(&self.get_key.get_key(value))
+    }
+
+    fn len(&self) -> usize {
+        self._value_map.len()
+    }
+
+    fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            iter: self._value_map.values(),
+        }
+    }
+
+    fn retain<P: FnMut(&T) -> bool>(&mut self, mut pred: P) {
+        self._value_map.retain(|_, v| pred(v));
+    }
+
+    fn drain(&mut self) -> Drain<'_, T, K> {
+        Drain {
+            iter: self._value_map.drain(..),
+        }
+    }
+
+    fn extract_if<'a, P: FnMut(&T) -> bool + 'a>(&'a mut self, pred: P) -> impl Iterator<Item=T> + 'a {
+        ExtractIf {
+            map: &mut self._value_map,
+            index: 0,
+            pred,
+        }
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        KeyIndexSet::contains_key(self, key)
+    }
+
+    fn get_by_key(&self, key: &K) -> Option<&T> {
+        KeyIndexSet::get_by_key(self, key)
+    }
+
+    fn remove_by_key(&mut self, key: &K) -> bool {
+        KeyIndexSet::remove_by_key(self, key)
+    }
+
+    fn take_by_key(&mut self, key: &K) -> Option<T> {
+        KeyIndexSet::take_by_key(self, key)
+    }
+
+    fn intersection_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.empty_like();
+
+        for (key, v) in self._value_map.iter() {
+            if let Some(other_v) = other._value_map.get(key) {
+                new_set.insert_value(self.pick(key, v, other_v, bias));
+            }
+        }
+
+        new_set
+    }
+
+    fn union_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.empty_like();
+
+        for (key, v) in self._value_map.iter() {
+            match other._value_map.get(key) {
+                Some(other_v) => new_set.insert_value(self.pick(key, v, other_v, bias)),
+                None => new_set.insert_value(v.clone()),
+            };
+        }
+
+        for (key, v) in other._value_map.iter() {
+            if !self._value_map.contains_key(key) {
+                new_set.insert_value(v.clone());
+            }
+        }
+
+        new_set
+    }
+
+    fn difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.empty_like();
+
+        for (key, v) in self._value_map.iter() {
+            if !other._value_map.contains_key(key) {
+                new_set.insert_value(v.clone());
+            }
+        }
+
+        new_set
+    }
+
+    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.difference(other);
+
+        for (key, v) in other._value_map.iter() {
+            if !self._value_map.contains_key(key) {
+                new_set.insert_value(v.clone());
+            }
+        }
+
+        new_set
+    }
+
+    fn union_with(&mut self, other: Self) {
+        for (key, v) in other._value_map {
+            self._value_map.entry(key).or_insert(v);
+        }
+    }
+
+    fn retain_intersection(&mut self, other: &Self) {
+        self._value_map.retain(|key, _| other._value_map.contains_key(key));
+    }
+
+    fn subtract(&mut self, other: &Self) {
+        self._value_map.retain(|key, _| !other._value_map.contains_key(key));
+    }
+
+    fn symmetric_difference_with(&mut self, other: Self) {
+        for (key, v) in other._value_map {
+            match self._value_map.entry(key) {
+                map::Entry::Occupied(entry) => { entry.shift_remove(); },
+                map::Entry::Vacant(entry) => { entry.insert(v); },
+            }
+        }
+    }
+}
+
+/// IntoIterator for KeyIndexSet
+impl<T, K, F> IntoIterator for KeyIndexSet<T, K, F> {
+    type Item = T;
+    type IntoIter = map::IntoValues<K, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self._value_map.into_values()
+    }
+}
+
+impl<'a, T, K, F> IntoIterator for &'a KeyIndexSet<T, K, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Iter<'a, T, K> {
+        Iter {
+            iter: self._value_map.values(),
+        }
+    }
+}
+
+/// Index for KeyIndexSet, panics when out of bounds
+impl<T, K, F> Index<usize> for KeyIndexSet<T, K, F> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self._value_map[index]
+    }
+}
+
+/// PartialEq for KeyIndexSet, the order doesn't matter
+impl<T, K, F> PartialEq for KeyIndexSet<T, K, F> where K: Eq + Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self._value_map.len() == other._value_map.len()
+        && self._value_map.keys().all(|key| other._value_map.contains_key(key))
+    }
+}
+
+/// Debug for KeyIndexSet
+impl<T, K, F> fmt::Debug for KeyIndexSet<T, K, F> where T: fmt::Debug, K: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyIndexSet")
+         .field("_value_map", &self._value_map)
+         .finish()
+    }
+}
+
+/// Extend for KeyIndexSet, elements refused under `DuplicatePolicy::Reject` are dropped
+impl<T, K, F> Extend<T> for KeyIndexSet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.insert_all(iter);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+pub struct Iter<'a, T, K> {
+    iter: map::Values<'a, K, T>,
+}
+
+impl<'a, T, K> Iterator for Iter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> DoubleEndedIterator for Iter<'_, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Iter<'_, T, K> {}
+impl<T, K> FusedIterator for Iter<'_, T, K> {}
+
+impl<T, K> Clone for Iter<'_, T, K> {
+    fn clone(&self) -> Self {
+        Iter { iter: self.iter.clone() }
+    }
+}
+
+/// Draining iterator of `KeyIndexSet`, in order
+pub struct Drain<'a, T, K> {
+    iter: map::Drain<'a, K, T>,
+}
+
+impl<T, K> Iterator for Drain<'_, T, K> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Drain<'_, T, K> {}
+impl<T, K> FusedIterator for Drain<'_, T, K> {}
+
+/// Iterator of `KeyIndexSet::extract_if`, in order.
+///
+/// The entries are visited in place by index, each match is shifted out of the
+/// map. Entries not visited yet stay in the set when the iterator is dropped or
+/// leaked.
+pub struct ExtractIf<'a, T, K, P> where K: Eq + Hash {
+    map: &'a mut IndexMap<K, T>,
+    index: usize,
+    pred: P,
+}
+
+impl<T, K, P> Iterator for ExtractIf<'_, T, K, P> where K: Eq + Hash, P: FnMut(&T) -> bool {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while let Some((_, v)) = self.map.get_index(self.index) {
+            if (self.pred)(v) {
+                return self.map.shift_remove_index(self.index).map(|(_, v)| v);
+            }
+            self.index += 1;
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len() - self.index))
+    }
+}
+
+impl<T, K, P> FusedIterator for ExtractIf<'_, T, K, P> where K: Eq + Hash, P: FnMut(&T) -> bool {}
@@ -0,0 +1,299 @@
+use std::borrow::{ Borrow };
+use std::collections::{ HashMap };
+use std::collections::hash_map;
+use std::hash::{ Hash };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::slice;
+use std::fmt;
+
+use crate::key_set::{ GetKey, GetKeyType };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyMultiSet
+
+/// A key set keeping every element, grouped by key in insertion order.
+///
+/// It's a multiset over keys, so it doesn't implement `KeySet`: `union` sums
+/// the counts of a key, `intersection` keeps the smaller count and `difference`
+/// subtracts them. The elements kept are always the first ones of a group.
+pub struct KeyMultiSet<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    len: usize,
+    _group_map: HashMap<K, Vec<T>>,
+}
+
+impl<T, K> KeyMultiSet<T, K> where K: Eq + Hash {
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+
+    pub fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
+        Self::from_intoiter_with(get_key, iter)
+    }
+}
+
+impl<T, K, F> KeyMultiSet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F) -> Self {
+        KeyMultiSet {
+            get_key,
+            len: 0,
+            _group_map: HashMap::new(),
+        }
+    }
+
+    pub fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.extend(iter);
+        this
+    }
+
+    /// Add `value` at the end of its group, never replaces.
+    pub fn insert(&mut self, value: T) {
+        let key = self.get_key.get_key(&value);
+
+        self._group_map.entry(key).or_default().push(value);
+        self.len += 1;
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self._group_map.contains_key(&self.get_key.get_key(value))
+    }
+
+    /// Multiset union, the groups of `other` are appended to ours.
+    pub fn union(&self, other: &Self) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.clone_like();
+
+        for (key, group) in other._group_map.iter() {
+            match new_set._group_map.get_mut(key) {
+                Some(new_group) => new_group.extend_from_slice(group),
+                None => {
+                    new_set._group_map.insert(new_set.get_key.get_key(&group[0]), group.clone());
+                },
+            }
+            new_set.len += group.len();
+        }
+
+        new_set
+    }
+
+    /// Multiset intersection, a key keeps the first `min` elements of our group.
+    pub fn intersection(&self, other: &Self) -> Self where T: Clone, F: Clone {
+        self.map_groups(|key, group| group.len().min(other.count(key)))
+    }
+
+    /// Multiset difference, a key keeps the first `ours - theirs` elements of our group.
+    pub fn difference(&self, other: &Self) -> Self where T: Clone, F: Clone {
+        self.map_groups(|key, group| group.len().saturating_sub(other.count(key)))
+    }
+
+    fn clone_like(&self) -> Self where T: Clone, F: Clone {
+        self.map_groups(|_, group| group.len())
+    }
+
+    /// Build a set from the first `keep(key, group)` elements of each group.
+    fn map_groups<G>(&self, mut keep: G) -> Self where T: Clone, F: Clone, G: FnMut(&K, &[T]) -> usize {
+        let mut new_set = KeyMultiSet::with_get_key(self.get_key.clone());
+
+        for (key, group) in self._group_map.iter() {
+            let n = keep(key, group);
+
+            if n > 0 {
+                new_set._group_map.insert(self.get_key.get_key(&group[0]), group[..n].to_vec());
+                new_set.len += n;
+            }
+        }
+
+        new_set
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> KeyMultiSet<T, K, F> where K: Eq + Hash {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._group_map.contains_key(key)
+    }
+
+    /// All the elements of `key` in insertion order, empty if there is none.
+    pub fn get_all<Q>(&self, key: &Q) -> &[T] where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._group_map.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn count<Q>(&self, key: &Q) -> usize where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.get_all(key).len()
+    }
+
+    /// Remove the last inserted element of `key`.
+    pub fn remove_one<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let group = self._group_map.get_mut(key)?;
+        let value = group.pop();
+
+        if group.is_empty() {
+            self._group_map.remove(key);
+        }
+        self.len -= 1;
+
+        value
+    }
+
+    /// Remove every element of `key`, in insertion order.
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let group = self._group_map.remove(key).unwrap_or_default();
+        self.len -= group.len();
+
+        group
+    }
+}
+
+impl<T, K, F> KeyMultiSet<T, K, F> {
+    /// Number of elements, counting each of a group.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of distinct keys.
+    pub fn key_count(&self) -> usize {
+        self._group_map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self._group_map.clear();
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            groups: self._group_map.values(),
+            group: [].iter(),
+            len: self.len,
+        }
+    }
+
+    pub fn iter_groups(&self) -> Groups<'_, T, K> {
+        Groups {
+            iter: self._group_map.iter(),
+        }
+    }
+
+    pub fn keys(&self) -> hash_map::Keys<'_, K, Vec<T>> {
+        self._group_map.keys()
+    }
+}
+
+/// IntoIterator for KeyMultiSet
+impl<T, K, F> IntoIterator for KeyMultiSet<T, K, F> {
+    type Item = T;
+    type IntoIter = std::iter::Flatten<hash_map::IntoValues<K, Vec<T>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self._group_map.into_values().flatten()
+    }
+}
+
+impl<'a, T, K, F> IntoIterator for &'a KeyMultiSet<T, K, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Iter<'a, T, K> {
+        self.iter()
+    }
+}
+
+/// PartialEq for KeyMultiSet, sets are equal when every key has the same count
+impl<T, K, F> PartialEq for KeyMultiSet<T, K, F> where K: Eq + Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+        && self._group_map.len() == other._group_map.len()
+        && self._group_map.iter().all(|(key, group)| other.count(key) == group.len())
+    }
+}
+
+/// Debug for KeyMultiSet
+impl<T, K, F> fmt::Debug for KeyMultiSet<T, K, F> where T: fmt::Debug, K: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyMultiSet")
+         .field("_group_map", &self._group_map)
+         .finish()
+    }
+}
+
+/// Extend for KeyMultiSet
+impl<T, K, F> Extend<T> for KeyMultiSet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+/// Iterator over every element of `KeyMultiSet`, a group's elements in insertion order
+pub struct Iter<'a, T, K> {
+    groups: hash_map::Values<'a, K, Vec<T>>,
+    group: slice::Iter<'a, T>,
+    len: usize,
+}
+
+impl<'a, T, K> Iterator for Iter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(v) = self.group.next() {
+                self.len -= 1;
+                return Some(v);
+            }
+            self.group = self.groups.next()?.iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T, K> ExactSizeIterator for Iter<'_, T, K> {}
+impl<T, K> FusedIterator for Iter<'_, T, K> {}
+
+impl<T, K> Clone for Iter<'_, T, K> {
+    fn clone(&self) -> Self {
+        Iter {
+            groups: self.groups.clone(),
+            group: self.group.clone(),
+            len: self.len,
+        }
+    }
+}
+
+/// Iterator over the groups of `KeyMultiSet`, as key and elements
+pub struct Groups<'a, T, K> {
+    iter: hash_map::Iter<'a, K, Vec<T>>,
+}
+
+impl<'a, T, K> Iterator for Groups<'a, T, K> {
+    type Item = (&'a K, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(key, group)| (key, group.as_slice()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Groups<'_, T, K> {}
+impl<T, K> FusedIterator for Groups<'_, T, K> {}
+
+impl<T, K> Clone for Groups<'_, T, K> {
+    fn clone(&self) -> Self {
+        Groups { iter: self.iter.clone() }
+    }
+}
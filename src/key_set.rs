@@ -2,46 +2,270 @@
 
 use std::collections::hash_map::{ HashMap };
 use std::collections::hash_map;
-use std::hash::{ Hash };
-use std::iter:: { Map, IntoIterator };
-use std::fmt;
+use std::collections::TryReserveError;
+use std::collections::hash_map::RandomState;
+use std::borrow::{ Borrow };
+use std::hash::{ BuildHasher, Hash };
+use std::error::{ Error };
+use std::iter:: { Chain, FusedIterator, IntoIterator };
+use std::mem;
+use std::ops::{
+    Deref, DerefMut,
+    BitOr, BitAnd, Sub, BitXor, BitOrAssign, BitAndAssign, SubAssign, BitXorAssign
+};
+use std::sync::{ Arc };
 use std::vec;
+use std::thread;
+use std::fmt;
 
 pub type GetKeyType<T, K> = fn(&T) -> K;
 pub type Map2SetType<T, K> = fn((K, T)) -> T;
+pub type BoxedGetKey<T, K> = Box<dyn Fn(&T) -> K>;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// GetKey
+
+/// Derive the key of an element.
+///
+/// Implemented for every `Fn(&T) -> K` (fn pointers, closures, `Box<dyn Fn>`)
+/// and for `SharedGetKey`.
+pub trait GetKey<T, K> {
+    fn get_key(&self, value: &T) -> K;
+}
+
+impl<T, K, F> GetKey<T, K> for F where F: Fn(&T) -> K {
+    fn get_key(&self, value: &T) -> K {
+        self(value)
+    }
+}
+
+/// Reference-counted key extractor, cheap to clone and shareable across threads.
+pub struct SharedGetKey<T, K> {
+    get_key: Arc<dyn Fn(&T) -> K + Send + Sync>,
+}
+
+impl<T, K> SharedGetKey<T, K> {
+    pub fn new(get_key: impl Fn(&T) -> K + Send + Sync + 'static) -> Self {
+        SharedGetKey {
+            get_key: Arc::new(get_key),
+        }
+    }
+}
+
+impl<T, K> Clone for SharedGetKey<T, K> {
+    fn clone(&self) -> Self {
+        SharedGetKey {
+            get_key: Arc::clone(&self.get_key),
+        }
+    }
+}
+
+impl<T, K> GetKey<T, K> for SharedGetKey<T, K> {
+    fn get_key(&self, value: &T) -> K {
+        (self.get_key)(value)
+    }
+}
+
+impl<T, K> From<Arc<dyn Fn(&T) -> K + Send + Sync>> for SharedGetKey<T, K> {
+    fn from(get_key: Arc<dyn Fn(&T) -> K + Send + Sync>) -> Self {
+        SharedGetKey { get_key }
+    }
+}
+
+impl<T, K> From<GetKeyType<T, K>> for SharedGetKey<T, K> where T: 'static, K: 'static {
+    fn from(get_key: GetKeyType<T, K>) -> Self {
+        SharedGetKey::new(get_key)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// DuplicatePolicy
+
+/// What `insert` does with an element whose key is already in the set.
+///
+/// `Merge` takes a `fn` pointer rather than a closure, so the policy stays a plain `Copy`
+/// value and a set is `Clone`, `Send` and `Sync` whatever it merges with.
+/// A closure that captures nothing coerces to it, state a merge needs belongs in the elements.
+#[derive(Default)]
+pub enum DuplicatePolicy<T> {
+    /// Keep the element already in the set, drop the new one.
+    KeepFirst,
+    /// Replace the element already in the set.
+    #[default]
+    KeepLast,
+    /// Refuse the new element, bulk inserts (`from_intoiter`, `extend`) skip it
+    /// and `try_extend` hands it back.
+    Reject,
+    /// Merge the new element into the one in the set, the key must not change.
+    Merge(fn(&mut T, T)),
+}
+
+impl<T> DuplicatePolicy<T> {
+    /// Settle `value` against the `existing` element of the same key.
+    pub(crate) fn resolve(&self, existing: &mut T, value: T) -> InsertOutcome<T> {
+        match self {
+            DuplicatePolicy::KeepFirst => InsertOutcome::Kept(value),
+            DuplicatePolicy::KeepLast => InsertOutcome::Replaced(mem::replace(existing, value)),
+            DuplicatePolicy::Reject => InsertOutcome::Rejected(value),
+            DuplicatePolicy::Merge(merge) => {
+                merge(existing, value);
+                InsertOutcome::Merged
+            },
+        }
+    }
+}
+
+impl<T> Clone for DuplicatePolicy<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for DuplicatePolicy<T> {}
+
+impl<T> fmt::Debug for DuplicatePolicy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuplicatePolicy::KeepFirst => write!(f, "KeepFirst"),
+            DuplicatePolicy::KeepLast => write!(f, "KeepLast"),
+            DuplicatePolicy::Reject => write!(f, "Reject"),
+            DuplicatePolicy::Merge(_) => write!(f, "Merge(..)"),
+        }
+    }
+}
+
+/// Which element a set operation keeps when both sides hold the same key.
+///
+/// Like `DuplicatePolicy::Merge`, `Merge` takes a `fn` pointer so the bias stays `Copy`.
+#[derive(Default)]
+pub enum Bias<T> {
+    /// Keep the element of `self`.
+    #[default]
+    Left,
+    /// Keep the element of `other`.
+    Right,
+    /// Build a new element from the left and the right one, the key must not change.
+    Merge(fn(&T, &T) -> T),
+}
+
+impl<T> Clone for Bias<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Bias<T> {}
+
+impl<T> fmt::Debug for Bias<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bias::Left => write!(f, "Left"),
+            Bias::Right => write!(f, "Right"),
+            Bias::Merge(_) => write!(f, "Merge(..)"),
+        }
+    }
+}
+
+/// What `insert` did.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InsertOutcome<T> {
+    /// The key was not in the set.
+    Inserted,
+    /// The old element was replaced and is handed back.
+    Replaced(T),
+    /// The old element was kept, the new one is handed back.
+    Kept(T),
+    /// The new element was refused and is handed back.
+    Rejected(T),
+    /// The new element was merged into the old one.
+    Merged,
+}
+
+impl<T> InsertOutcome<T> {
+    /// Whether the key was new to the set.
+    pub fn is_fresh(&self) -> bool {
+        matches!(self, InsertOutcome::Inserted)
+    }
+
+    /// The element that didn't end up in the set, if any.
+    pub fn into_displaced(self) -> Option<T> {
+        match self {
+            InsertOutcome::Replaced(value)
+            | InsertOutcome::Kept(value)
+            | InsertOutcome::Rejected(value) => Some(value),
+            InsertOutcome::Inserted | InsertOutcome::Merged => None,
+        }
+    }
+}
 
 
 ////////////////////////////////////////////////////////////////////////////////
-/// KeySet
+// KeySet
 
 pub trait KeySet <T, K> {
+    type KeyFn: GetKey<T, K>;
+    type Iter<'a>: Iterator<Item=&'a T> + ExactSizeIterator + FusedIterator + Clone
+    where Self: 'a, T: 'a;
+    type Drain<'a>: Iterator<Item=T> where Self: 'a, T: 'a;
+
     /**
     * Create KeySet
     */
-    fn new(get_key: GetKeyType<T, K>) -> Self;
+    fn with_get_key(get_key: Self::KeyFn) -> Self;
+
+    fn from_intoiter_with(get_key: Self::KeyFn, iter: impl IntoIterator<Item=T>) -> Self;
 
-    fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self;
+    fn new(get_key: GetKeyType<T, K>) -> Self where Self: Sized, Self::KeyFn: From<GetKeyType<T, K>> {
+        Self::with_get_key(get_key.into())
+    }
+
+    fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self
+    where Self: Sized, Self::KeyFn: From<GetKeyType<T, K>>
+    {
+        Self::from_intoiter_with(get_key.into(), iter)
+    }
 
     /**
     * Operate KeySet elem
     */
-    fn insert(&mut self, value: T);
+    fn insert(&mut self, value: T) -> InsertOutcome<T>;
+    fn try_insert(&mut self, value: T) -> Result<&T, T>;
     fn contains(&self, value: &T) -> bool;
     fn remove(&mut self, value: &T) -> bool;
     fn take(&mut self, value: &T) -> Option<T>;
-    fn get(&mut self, value: &T) -> Option<&T>;
+    fn get(&self, value: &T) -> Option<&T>;
     fn len(&self) -> usize;
-    fn iter(&self) -> vec::IntoIter<&T>;
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /**
+    * Remove KeySet elems in bulk
+    */
+    fn retain<P: FnMut(&T) -> bool>(&mut self, pred: P);
+    /// Empty the set, yielding the removed elements.
+    fn drain(&mut self) -> Self::Drain<'_>;
+    /// Lazily remove and yield the elements matching `pred`,
+    /// the elements not yet visited stay if the iterator is dropped early.
+    fn extract_if<'a, P: FnMut(&T) -> bool + 'a>(&'a mut self, pred: P) -> impl Iterator<Item=T> + 'a;
+
+    /**
+    * Operate KeySet elem by key, without building a whole `T`
+    */
+    fn contains_key(&self, key: &K) -> bool;
+    fn get_by_key(&self, key: &K) -> Option<&T>;
+    fn remove_by_key(&mut self, key: &K) -> bool;
+    fn take_by_key(&mut self, key: &K) -> Option<T>;
 
     /**
     * Check KeySet relationship
     */
     fn is_subset(&self, other: &Self) -> bool {
-        self.iter().all(|x| other.contains(&x))
+        self.iter().all(|x| other.contains(x))
     }
 
     fn is_superset(&self, other: &Self) -> bool {
-        other.iter().all(|x| self.contains(&x))
+        other.iter().all(|x| self.contains(x))
     }
 
     fn is_empty(&self) -> bool {
@@ -53,17 +277,42 @@ pub trait KeySet <T, K> {
     }
 
     /**
-    * Operate with other KeySet
+    * Operate with other KeySet, the result carries a clone of `get_key`
+    *
+    * When both sets hold the same key, `intersection` and `union` keep the element
+    * of `self` (`Bias::Left`), the `_by` variants take the bias explicitly.
+    */
+    fn intersection<'a>(&'a self, other: &'a Self) -> Self
+    where Self: Sized, T: Clone, Self::KeyFn: Clone
+    {
+        self.intersection_by(other, Bias::Left)
+    }
+    fn union<'a>(&'a self, other: &'a Self) -> Self
+    where Self: Sized, T: Clone, Self::KeyFn: Clone
+    {
+        self.union_by(other, Bias::Left)
+    }
+    fn intersection_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self
+    where T: Clone, Self::KeyFn: Clone;
+    fn union_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self
+    where T: Clone, Self::KeyFn: Clone;
+    fn difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, Self::KeyFn: Clone;
+    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, Self::KeyFn: Clone;
+
+    /**
+    * Operate with other KeySet in place, elements are moved instead of cloned
+    *
+    * Like `union`, `self` keeps its own element for a key held by both sets.
     */
-    fn intersection<'a>(&'a self, other: &'a Self) -> Self;
-    fn union<'a>(&'a self, other: &'a Self) -> Self;
-    fn difference<'a>(&'a self, other: &'a Self) -> Self;
-    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self;
+    fn union_with(&mut self, other: Self) where Self: Sized;
+    fn retain_intersection(&mut self, other: &Self);
+    fn subtract(&mut self, other: &Self);
+    fn symmetric_difference_with(&mut self, other: Self) where Self: Sized;
 }
 
 
 ////////////////////////////////////////////////////////////////////////////////
-/// Utils
+// Utils
 
 pub fn debug_key<T: fmt::Debug>(value: &T) -> String {
     format!("{:?}", value)
@@ -71,156 +320,1234 @@ pub fn debug_key<T: fmt::Debug>(value: &T) -> String {
 
 
 ////////////////////////////////////////////////////////////////////////////////
-/// KeyHashSet
+// KeyHashSet
+
+pub struct KeyHashSet<T, K: Hash, F = GetKeyType<T, K>, S = RandomState> {
+    get_key: F,
+    duplicate_policy: DuplicatePolicy<T>,
+    key_change_policy: KeyChangePolicy,
+    _value_map: HashMap<K, T, S>,
+}
+
+impl<T, K> KeyHashSet<T, K> where K: Eq + Hash {
+    /// Keep `KeyHashSet::new(debug_key)` inferring the plain fn pointer form,
+    /// stateful extractors go through `with_get_key`.
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+
+    pub fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.insert_all(iter);
+        this
+    }
+}
+
+impl<T, K, F> KeyHashSet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F) -> Self {
+        Self::with_hasher(get_key, RandomState::new())
+    }
+
+    pub fn with_capacity(get_key: F, capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(get_key, capacity, RandomState::new())
+    }
+
+    pub fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.insert_all(iter);
+        this
+    }
 
-pub struct KeyHashSet<T, K: Hash> {
-    get_key: GetKeyType<T, K>,
-    _value_map: HashMap<K, T>,
+    pub fn from_intoiter_with_policy(
+        get_key: F,
+        policy: DuplicatePolicy<T>,
+        iter: impl IntoIterator<Item=T>
+    ) -> Self
+    {
+        let mut this = Self::with_get_key(get_key);
+        this.duplicate_policy = policy;
+        this.insert_all(iter);
+        this
+    }
 }
 
-impl <T, K> KeySet<T, K> for KeyHashSet<T, K> where T: Clone, K: Eq + Hash {
-    fn new(get_key: GetKeyType<T, K>) -> Self {
-        let _value_map:HashMap<K, T> = HashMap::new();
+impl<T, K, F, S> KeyHashSet<T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    pub fn with_hasher(get_key: F, hash_builder: S) -> Self {
+        let _value_map:HashMap<K, T, S> = HashMap::with_hasher(hash_builder);
 
         KeyHashSet {
             get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            key_change_policy: KeyChangePolicy::default(),
             _value_map,
         }
     }
 
-    fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
-        let mut this = Self::new(get_key);
-        for e in iter {
-            this.insert(e);
-        }
+    pub fn with_capacity_and_hasher(get_key: F, capacity: usize, hash_builder: S) -> Self {
+        let mut this = Self::with_hasher(get_key, hash_builder);
+        this._value_map.reserve(capacity);
         this
     }
 
-    fn insert(&mut self, value:T) {
-        let key = (self.get_key)(&value);
+    pub fn hasher(&self) -> &S {
+        self._value_map.hasher()
+    }
 
-        self._value_map.insert(key, value);
+    pub fn capacity(&self) -> usize {
+        self._value_map.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self._value_map.reserve(additional);
+    }
+
+    /// Like `reserve`, but allocation failure is returned instead of aborting.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self._value_map.try_reserve(additional)
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self._value_map.shrink_to_fit();
+    }
+
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self._value_map.shrink_to(min_capacity);
+    }
+
+    /// Empty set with the same key function and policies.
+    fn empty_like(&self) -> Self where F: Clone, S: Clone {
+        KeyHashSet {
+            get_key: self.get_key.clone(),
+            duplicate_policy: self.duplicate_policy,
+            key_change_policy: self.key_change_policy,
+            _value_map: HashMap::with_hasher(self.hasher().clone()),
+        }
+    }
+
+    /// Clone the elements of a set-operation iterator into a set like `self`,
+    /// the keys coming out of those iterators are unique.
+    fn collect_like<'a>(&self, iter: impl Iterator<Item=&'a T>) -> Self
+    where T: Clone + 'a, F: Clone, S: Clone
+    {
+        let mut new_set = self.empty_like();
+
+        for v in iter {
+            new_set.insert_value(v.clone());
+        }
+
+        new_set
+    }
+
+    /// The element kept under `bias` for a key held by both sides.
+    fn pick(&self, key: &K, left: &T, right: &T, bias: Bias<T>) -> T where T: Clone {
+        match bias {
+            Bias::Left => left.clone(),
+            Bias::Right => right.clone(),
+            Bias::Merge(merge) => {
+                let merged = merge(left, right);
+                assert_same_key(&self.get_key, key, &merged);
+
+                merged
+            },
+        }
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy<T> {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy<T>) {
+        self.duplicate_policy = policy;
     }
 
-    fn contains(&self, value: &T) -> bool {
-        let key = &(self.get_key)(value);
 
+    fn insert_value(&mut self, value: T) -> InsertOutcome<T> {
+        let key = self.get_key.get_key(&value);
+
+        match self._value_map.entry(key) {
+            hash_map::Entry::Occupied(mut entry) => {
+                let outcome = self.duplicate_policy.resolve(entry.get_mut(), value);
+
+                if let InsertOutcome::Merged = outcome {
+                    assert_same_key(&self.get_key, entry.key(), entry.get());
+                }
+                outcome
+            },
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                InsertOutcome::Inserted
+            },
+        }
+    }
+
+    /// Bulk insert, the elements refused under `DuplicatePolicy::Reject` are handed back.
+    fn insert_all(&mut self, iter: impl IntoIterator<Item=T>) -> Vec<T> {
+        let iter = iter.into_iter();
+
+        // the same guess as `HashMap::extend`, half of the hint may be duplicates
+        let hint = iter.size_hint().0;
+        self._value_map.reserve(if self._value_map.is_empty() { hint } else { hint.div_ceil(2) });
+
+        let mut rejected = Vec::new();
+
+        for value in iter {
+            if let InsertOutcome::Rejected(value) = self.insert_value(value) {
+                rejected.push(value);
+            }
+        }
+
+        rejected
+    }
+
+    /// Like `extend`, but the elements refused under `DuplicatePolicy::Reject`
+    /// are handed back, in iteration order, instead of dropped.
+    pub fn try_extend(&mut self, iter: impl IntoIterator<Item=T>) -> Result<(), Vec<T>> {
+        let rejected = self.insert_all(iter);
+
+        if rejected.is_empty() { Ok(()) } else { Err(rejected) }
+    }
+
+    pub fn key_change_policy(&self) -> KeyChangePolicy {
+        self.key_change_policy
+    }
+
+    pub fn set_key_change_policy(&mut self, policy: KeyChangePolicy) {
+        self.key_change_policy = policy;
+    }
+
+    /// Mutable access to the element with the key of `value`,
+    /// the key is checked again when the guard is released.
+    pub fn get_mut(&mut self, value: &T) -> Option<GuardMut<'_, T, K, F, S>> {
+        let key = self.get_key.get_key(value);
+
+        self.get_mut_by_key(&key)
+    }
+
+    pub fn get_mut_by_key<Q>(&mut self, key: &Q) -> Option<GuardMut<'_, T, K, F, S>>
+    where K: Borrow<Q>, Q: ?Sized + Eq + Hash
+    {
+        let key = self.get_key.get_key(self._value_map.get(key)?);
+
+        Some(GuardMut {
+            set: self,
+            key: Some(key),
+        })
+    }
+
+    /// Entry for the key of `value`, holding `value` to insert or replace with.
+    /// The key is computed and hashed once.
+    pub fn entry(&mut self, value: T) -> Entry<'_, T, K, F> {
+        let key = self.get_key.get_key(&value);
+
+        self.entry_with(key, value)
+    }
+
+    /// Entry for `key`, its element is built with `or_insert_with` and the like.
+    pub fn entry_by_key(&mut self, key: K) -> Entry<'_, T, K, F, ()> {
+        self.entry_with(key, ())
+    }
+
+    fn entry_with<V>(&mut self, key: K, value: V) -> Entry<'_, T, K, F, V> {
+        let get_key = &self.get_key;
+
+        match self._value_map.entry(key) {
+            hash_map::Entry::Occupied(entry) => Entry::Occupied(OccupiedEntry { get_key, entry, value }),
+            hash_map::Entry::Vacant(entry) => Entry::Vacant(VacantEntry { get_key, entry, value }),
+        }
+    }
+}
+
+impl<T, K, F, S> KeyHashSet<T, K, F, S> where K: Hash {
+    pub fn keys(&self) -> Keys<'_, T, K> {
+        Keys {
+            iter: self._value_map.keys(),
+        }
+    }
+
+    pub fn iter_with_keys(&self) -> IterWithKeys<'_, T, K> {
+        IterWithKeys {
+            iter: self._value_map.iter(),
+        }
+    }
+
+    pub(crate) fn get_key_fn(&self) -> &F {
+        &self.get_key
+    }
+}
+
+/// Lazy set operations, yielding borrowed elements like `std::collections::HashSet`.
+impl<T, K, F, S> KeyHashSet<T, K, F, S> where K: Eq + Hash, S: BuildHasher {
+    /// Elements of `self` whose key is also in `other`.
+    pub fn intersection_iter<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, K, F, S> {
+        Intersection {
+            iter: self._value_map.iter(),
+            other,
+        }
+    }
+
+    /// Elements of `self`, then those of `other` whose key isn't in `self`.
+    pub fn union_iter<'a>(&'a self, other: &'a Self) -> Union<'a, T, K, F, S> {
+        Union {
+            iter: self._value_map.values().chain(other.difference_iter(self)),
+        }
+    }
+
+    /// Elements of `self` whose key isn't in `other`.
+    pub fn difference_iter<'a>(&'a self, other: &'a Self) -> Difference<'a, T, K, F, S> {
+        Difference {
+            iter: self._value_map.iter(),
+            other,
+        }
+    }
+
+    /// Elements whose key is in exactly one of `self` and `other`.
+    pub fn symmetric_difference_iter<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T, K, F, S> {
+        SymmetricDifference {
+            left: self.difference_iter(other),
+            right: other.difference_iter(self),
+        }
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F, S> KeyHashSet<T, K, F, S> where K: Eq + Hash, S: BuildHasher {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
         self._value_map.contains_key(key)
     }
 
-    // Rust doesn't open the constructor method for struct Draw
-    // IndexHashMap drain range
-    // pub fn drain(&mut self) -> Map<HashMap::map::Drain<'_, K, T>, Map2SetType<T, K>> {
-    //     self._value_map.drain().map(|(_, v)| v)
-    // }
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.get(key)
+    }
 
-    fn remove(&mut self, value:&T) -> bool {
-        let key = &(self.get_key)(value);
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.remove(key).is_some()
+    }
+
+    pub fn take_by_key<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._value_map.remove(key)
+    }
+}
+
+/// Element operations, usable with any hasher.
+impl<T, K, F, S> KeyHashSet<T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    pub fn insert(&mut self, value:T) -> InsertOutcome<T> {
+        self.insert_value(value)
+    }
+
+    pub fn try_insert(&mut self, value: T) -> Result<&T, T> {
+        let key = self.get_key.get_key(&value);
 
-        match self._value_map.remove(key) {
-            None => false,
-            _ => true
+        match self._value_map.entry(key) {
+            hash_map::Entry::Occupied(_) => Err(value),
+            hash_map::Entry::Vacant(entry) => Ok(entry.insert(value)),
         }
     }
 
-    fn take(&mut self, value:&T) -> Option<T> {
-        let key = &(self.get_key)(value);
+    pub fn contains(&self, value: &T) -> bool {
+        let key = &self.get_key.get_key(value);
+
+        self._value_map.contains_key(key)
+    }
+
+    pub fn remove(&mut self, value:&T) -> bool {
+        let key = &self.get_key.get_key(value);
+
+        self._value_map.remove(key).is_some()
+    }
+
+    pub fn take(&mut self, value:&T) -> Option<T> {
+        let key = &self.get_key.get_key(value);
 
         self._value_map.remove(key)
     }
 
-    fn get(&mut self, value:&T) -> Option<&T> {
-        let key = &(self.get_key)(value);
+    pub fn get(&self, value:&T) -> Option<&T> {
+        let key = &self.get_key.get_key(value);
 
         self._value_map.get(key)
     }
 
-    fn len(&self) -> usize {
-        return self._value_map.len();
+    pub fn len(&self) -> usize {
+        self._value_map.len()
     }
 
-    fn iter(&self) -> vec::IntoIter<&T> {
-        let res: Vec<&T> = self._value_map.values ().collect();
-        res.into_iter()
+    pub fn is_empty(&self) -> bool {
+        self._value_map.is_empty()
     }
 
-    fn intersection<'a>(&'a self, other: &'a Self) -> Self {
-        let mut new_set = KeyHashSet::new(self.get_key);
-        for v in self.iter().chain(other.iter()) {
-            new_set.insert(v.clone())
+    pub fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            iter: self._value_map.values(),
         }
+    }
 
-        new_set
+    pub fn retain<P: FnMut(&T) -> bool>(&mut self, mut pred: P) {
+        self._value_map.retain(|_, v| pred(v));
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, T, K> {
+        Drain {
+            iter: self._value_map.drain(),
+        }
     }
 
-    fn union<'a>(&'a self, other: &'a Self) -> Self {
-        let mut new_set = KeyHashSet::new(self.get_key);
+    pub fn extract_if<P: FnMut(&T) -> bool>(&mut self, pred: P) -> ExtractIf<'_, T, K, P, S> {
+        ExtractIf::new(&mut self._value_map, &self.get_key, pred)
+    }
 
-        for v in self.iter().filter(|v| other.contains(v)) {
-            new_set.insert(v.clone())
+    pub fn union_with(&mut self, other: Self) {
+        for (key, v) in other._value_map {
+            self._value_map.entry(key).or_insert(v);
         }
+    }
 
-        new_set
+    pub fn retain_intersection(&mut self, other: &Self) {
+        self._value_map.retain(|key, _| other._value_map.contains_key(key));
+    }
+
+    pub fn subtract(&mut self, other: &Self) {
+        self._value_map.retain(|key, _| !other._value_map.contains_key(key));
+    }
+
+    pub fn symmetric_difference_with(&mut self, other: Self) {
+        for (key, v) in other._value_map {
+            match self._value_map.entry(key) {
+                hash_map::Entry::Occupied(entry) => { entry.remove(); },
+                hash_map::Entry::Vacant(entry) => { entry.insert(v); },
+            }
+        }
+    }
+}
+
+/// Set operations, the result gets a clone of the hasher of `self`.
+impl<T, K, F, S> KeyHashSet<T, K, F, S>
+where T: Clone, K: Eq + Hash, S: BuildHasher + Clone, F: GetKey<T, K> + Clone
+{
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.intersection_by(other, Bias::Left)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.union_by(other, Bias::Left)
     }
 
-    fn difference<'a>(&'a self, other: &'a Self) -> Self {
-        let mut new_set = KeyHashSet::new(self.get_key);
+    pub fn intersection_by(&self, other: &Self, bias: Bias<T>) -> Self {
+        let mut new_set = self.empty_like();
 
-        for v in self.iter().filter(|v| !other.contains(v)) {
-            new_set.insert(v.clone())
+        for (key, v) in self._value_map.iter() {
+            if let Some(other_v) = other._value_map.get(key) {
+                new_set.insert(self.pick(key, v, other_v, bias));
+            }
         }
 
         new_set
     }
 
-    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self {
-        let mut new_set = KeyHashSet::new(self.get_key);
+    pub fn union_by(&self, other: &Self, bias: Bias<T>) -> Self {
+        let mut new_set = self.empty_like();
 
-        for v in self.iter().filter(|v| !other.contains(v)) {
-            new_set.insert(v.clone())
+        for (key, v) in self._value_map.iter() {
+            match other._value_map.get(key) {
+                Some(other_v) => new_set.insert(self.pick(key, v, other_v, bias)),
+                None => new_set.insert(v.clone()),
+            };
         }
 
-        for v in other.iter().filter(|v| !self.contains(v)) {
-            new_set.insert(v.clone())
+        for v in other.difference_iter(self) {
+            new_set.insert(v.clone());
         }
 
         new_set
     }
-}
 
-/// IntoIterator for KeyHashSet
-impl<T, K> IntoIterator for KeyHashSet<T, K> where K: Hash {
-    type Item = T;
-    type IntoIter = Map<hash_map::IntoIter<K, T>, Map2SetType<T, K>>;
+    pub fn difference(&self, other: &Self) -> Self {
+        self.collect_like(self.difference_iter(other))
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        self._value_map.into_iter().map(|(_, v)| v)
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.collect_like(self.symmetric_difference_iter(other))
     }
 }
 
-/// PartialEq for KeyHashSet
-impl<T, K> PartialEq for KeyHashSet<T, K> where T: Clone, K: Eq + Hash {
-    fn eq(&self, other: &Self) -> bool {
-        self.is_subset(other) && other.is_subset(self)
+/// The `KeySet` constructors build the hasher with `S::default()`,
+/// a hasher without `Default` goes through `with_hasher` and the inherent methods.
+impl<T, K, F, S> KeySet<T, K> for KeyHashSet<T, K, F, S>
+where K: Eq + Hash, F: GetKey<T, K>, S: BuildHasher + Clone + Default
+{
+    type KeyFn = F;
+    type Iter<'a> = Iter<'a, T, K> where Self: 'a, T: 'a;
+    type Drain<'a> = Drain<'a, T, K> where Self: 'a, T: 'a;
+
+    fn with_get_key(get_key: F) -> Self {
+        KeyHashSet::with_hasher(get_key, S::default())
     }
-}
 
-/// Debug for KeyHashSet
-impl<T, K> fmt::Debug for KeyHashSet<T, K> where T: Clone + fmt::Debug, K: fmt::Debug + Hash {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("KeyHashSet")
-         .field("_value_map", &self._value_map)
-         .finish()
+    fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = KeyHashSet::with_hasher(get_key, S::default());
+        this.insert_all(iter);
+        this
+    }
+
+    fn insert(&mut self, value:T) -> InsertOutcome<T> {
+        KeyHashSet::insert(self, value)
     }
-}
 
-/// Extend for KeyHashSet
+    fn try_insert(&mut self, value: T) -> Result<&T, T> {
+        KeyHashSet::try_insert(self, value)
+    }
 
-impl<T, K> Extend<T> for KeyHashSet<T, K> where T: Clone, K: Hash + Eq {
-    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        for item in iter {
-            self.insert(item);
-        }
+    fn contains(&self, value: &T) -> bool {
+        KeyHashSet::contains(self, value)
+    }
+
+    fn remove(&mut self, value:&T) -> bool {
+        KeyHashSet::remove(self, value)
+    }
+
+    fn take(&mut self, value:&T) -> Option<T> {
+        KeyHashSet::take(self, value)
+    }
+
+    fn get(&self, value:&T) -> Option<&T> {
+        KeyHashSet::get(self, value)
+    }
+
+    fn len(&self) -> usize {
+        KeyHashSet::len(self)
+    }
+
+    fn iter(&self) -> Iter<'_, T, K> {
+        KeyHashSet::iter(self)
+    }
+
+    fn retain<P: FnMut(&T) -> bool>(&mut self, pred: P) {
+        KeyHashSet::retain(self, pred)
+    }
+
+    fn drain(&mut self) -> Drain<'_, T, K> {
+        KeyHashSet::drain(self)
+    }
+
+    fn extract_if<'a, P: FnMut(&T) -> bool + 'a>(&'a mut self, pred: P) -> impl Iterator<Item=T> + 'a {
+        KeyHashSet::extract_if(self, pred)
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        KeyHashSet::contains_key(self, key)
+    }
+
+    fn get_by_key(&self, key: &K) -> Option<&T> {
+        KeyHashSet::get_by_key(self, key)
+    }
+
+    fn remove_by_key(&mut self, key: &K) -> bool {
+        KeyHashSet::remove_by_key(self, key)
+    }
+
+    fn take_by_key(&mut self, key: &K) -> Option<T> {
+        KeyHashSet::take_by_key(self, key)
+    }
+
+    fn intersection_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        KeyHashSet::intersection_by(self, other, bias)
+    }
+
+    fn union_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        KeyHashSet::union_by(self, other, bias)
+    }
+
+    fn difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        KeyHashSet::difference(self, other)
+    }
+
+    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        KeyHashSet::symmetric_difference(self, other)
+    }
+
+    fn union_with(&mut self, other: Self) {
+        KeyHashSet::union_with(self, other)
+    }
+
+    fn retain_intersection(&mut self, other: &Self) {
+        KeyHashSet::retain_intersection(self, other)
+    }
+
+    fn subtract(&mut self, other: &Self) {
+        KeyHashSet::subtract(self, other)
+    }
+
+    fn symmetric_difference_with(&mut self, other: Self) {
+        KeyHashSet::symmetric_difference_with(self, other)
+    }
+}
+
+/// IntoIterator for KeyHashSet
+impl<T, K, F, S> IntoIterator for KeyHashSet<T, K, F, S> where K: Hash {
+    type Item = T;
+    type IntoIter = hash_map::IntoValues<K, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self._value_map.into_values()
+    }
+}
+
+/// PartialEq for KeyHashSet
+impl<T, K, F, S> PartialEq for KeyHashSet<T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self._value_map.len() == other._value_map.len() && self.keys().all(|key| other._value_map.contains_key(key))
+    }
+}
+
+/// Debug for KeyHashSet
+impl<T, K, F, S> fmt::Debug for KeyHashSet<T, K, F, S> where T: fmt::Debug, K: fmt::Debug + Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyHashSet")
+         .field("_value_map", &self._value_map)
+         .finish()
+    }
+}
+
+/// Extend for KeyHashSet, elements refused under `DuplicatePolicy::Reject` are dropped
+impl<T, K, F, S> Extend<T> for KeyHashSet<T, K, F, S> where K: Hash + Eq, S: BuildHasher, F: GetKey<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.insert_all(iter);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+pub struct Iter<'a, T, K> {
+    iter: hash_map::Values<'a, K, T>,
+}
+
+pub struct Keys<'a, T, K> {
+    iter: hash_map::Keys<'a, K, T>,
+}
+
+pub struct IterWithKeys<'a, T, K> {
+    iter: hash_map::Iter<'a, K, T>,
+}
+
+impl<'a, T, K> Iterator for Iter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T, K> Iterator for Keys<'a, T, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T, K> Iterator for IterWithKeys<'a, T, K> {
+    type Item = (&'a K, &'a T);
+
+    fn next(&mut self) -> Option<(&'a K, &'a T)> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Draining iterator of `KeyHashSet`
+pub struct Drain<'a, T, K> {
+    iter: hash_map::Drain<'a, K, T>,
+}
+
+impl<T, K> Iterator for Drain<'_, T, K> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Iterator of `KeyHashSet::extract_if`.
+///
+/// The keys are taken up front and each element looked up as it is visited,
+/// the unvisited ones stay in the set when the iterator is dropped or leaked.
+pub struct ExtractIf<'a, T, K, P, S = RandomState> {
+    map: &'a mut HashMap<K, T, S>,
+    keys: vec::IntoIter<K>,
+    pred: P,
+}
+
+impl<'a, T, K, P, S> ExtractIf<'a, T, K, P, S> {
+    pub(crate) fn new<F: GetKey<T, K>>(map: &'a mut HashMap<K, T, S>, get_key: &F, pred: P) -> Self {
+        let keys: Vec<K> = map.values().map(|v| get_key.get_key(v)).collect();
+
+        Self {
+            map,
+            keys: keys.into_iter(),
+            pred,
+        }
+    }
+}
+
+impl<T, K, P, S> Iterator for ExtractIf<'_, T, K, P, S> where K: Eq + Hash, S: BuildHasher, P: FnMut(&T) -> bool {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        for key in self.keys.by_ref() {
+            if self.map.get(&key).is_some_and(&mut self.pred) {
+                return self.map.remove(&key);
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.keys.len()))
+    }
+}
+
+impl<T, K> ExactSizeIterator for Drain<'_, T, K> {}
+impl<T, K> FusedIterator for Drain<'_, T, K> {}
+impl<T, K, P, S> FusedIterator for ExtractIf<'_, T, K, P, S> where K: Eq + Hash, S: BuildHasher, P: FnMut(&T) -> bool {}
+
+impl<T, K> ExactSizeIterator for Iter<'_, T, K> {}
+impl<T, K> ExactSizeIterator for Keys<'_, T, K> {}
+impl<T, K> ExactSizeIterator for IterWithKeys<'_, T, K> {}
+
+impl<T, K> FusedIterator for Iter<'_, T, K> {}
+impl<T, K> FusedIterator for Keys<'_, T, K> {}
+impl<T, K> FusedIterator for IterWithKeys<'_, T, K> {}
+
+impl<T, K> Clone for Iter<'_, T, K> {
+    fn clone(&self) -> Self {
+        Iter { iter: self.iter.clone() }
+    }
+}
+
+impl<T, K> Clone for Keys<'_, T, K> {
+    fn clone(&self) -> Self {
+        Keys { iter: self.iter.clone() }
+    }
+}
+
+impl<T, K> Clone for IterWithKeys<'_, T, K> {
+    fn clone(&self) -> Self {
+        IterWithKeys { iter: self.iter.clone() }
+    }
+}
+
+impl<'a, T, K, F, S> IntoIterator for &'a KeyHashSet<T, K, F, S> where K: Hash {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Iter<'a, T, K> {
+        Iter {
+            iter: self._value_map.values(),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Operators
+
+/// `&a | &b` is `a.union(&b)`
+impl<T, K, F, S> BitOr<&KeyHashSet<T, K, F, S>> for &KeyHashSet<T, K, F, S>
+where T: Clone, K: Eq + Hash, F: GetKey<T, K> + Clone, S: BuildHasher + Clone
+{
+    type Output = KeyHashSet<T, K, F, S>;
+
+    fn bitor(self, rhs: &KeyHashSet<T, K, F, S>) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+/// `&a & &b` is `a.intersection(&b)`
+impl<T, K, F, S> BitAnd<&KeyHashSet<T, K, F, S>> for &KeyHashSet<T, K, F, S>
+where T: Clone, K: Eq + Hash, F: GetKey<T, K> + Clone, S: BuildHasher + Clone
+{
+    type Output = KeyHashSet<T, K, F, S>;
+
+    fn bitand(self, rhs: &KeyHashSet<T, K, F, S>) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+/// `&a - &b` is `a.difference(&b)`
+impl<T, K, F, S> Sub<&KeyHashSet<T, K, F, S>> for &KeyHashSet<T, K, F, S>
+where T: Clone, K: Eq + Hash, F: GetKey<T, K> + Clone, S: BuildHasher + Clone
+{
+    type Output = KeyHashSet<T, K, F, S>;
+
+    fn sub(self, rhs: &KeyHashSet<T, K, F, S>) -> Self::Output {
+        self.difference(rhs)
+    }
+}
+
+/// `&a ^ &b` is `a.symmetric_difference(&b)`
+impl<T, K, F, S> BitXor<&KeyHashSet<T, K, F, S>> for &KeyHashSet<T, K, F, S>
+where T: Clone, K: Eq + Hash, F: GetKey<T, K> + Clone, S: BuildHasher + Clone
+{
+    type Output = KeyHashSet<T, K, F, S>;
+
+    fn bitxor(self, rhs: &KeyHashSet<T, K, F, S>) -> Self::Output {
+        self.symmetric_difference(rhs)
+    }
+}
+
+/// `a |= &b` clones in the elements of `b` whose key isn't in `a`
+impl<T, K, F, S> BitOrAssign<&KeyHashSet<T, K, F, S>> for KeyHashSet<T, K, F, S>
+where T: Clone, K: Eq + Hash, S: BuildHasher, F: GetKey<T, K>
+{
+    fn bitor_assign(&mut self, rhs: &KeyHashSet<T, K, F, S>) {
+        for v in rhs.iter() {
+            if let hash_map::Entry::Vacant(entry) = self._value_map.entry(self.get_key.get_key(v)) {
+                entry.insert(v.clone());
+            }
+        }
+    }
+}
+
+/// `a &= &b` drops the elements of `a` whose key isn't in `b`
+impl<T, K, F, S> BitAndAssign<&KeyHashSet<T, K, F, S>> for KeyHashSet<T, K, F, S>
+where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K>
+{
+    fn bitand_assign(&mut self, rhs: &KeyHashSet<T, K, F, S>) {
+        self.retain_intersection(rhs);
+    }
+}
+
+/// `a -= &b` drops the elements of `a` whose key is in `b`
+impl<T, K, F, S> SubAssign<&KeyHashSet<T, K, F, S>> for KeyHashSet<T, K, F, S>
+where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K>
+{
+    fn sub_assign(&mut self, rhs: &KeyHashSet<T, K, F, S>) {
+        self.subtract(rhs);
+    }
+}
+
+/// `a ^= &b` drops the shared keys and clones in the rest of `b`
+impl<T, K, F, S> BitXorAssign<&KeyHashSet<T, K, F, S>> for KeyHashSet<T, K, F, S>
+where T: Clone, K: Eq + Hash, S: BuildHasher, F: GetKey<T, K>
+{
+    fn bitxor_assign(&mut self, rhs: &KeyHashSet<T, K, F, S>) {
+        for v in rhs.iter() {
+            match self._value_map.entry(self.get_key.get_key(v)) {
+                hash_map::Entry::Occupied(entry) => { entry.remove(); },
+                hash_map::Entry::Vacant(entry) => { entry.insert(v.clone()); },
+            }
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Set operation iterators
+
+pub struct Intersection<'a, T, K: Hash, F, S> {
+    iter: hash_map::Iter<'a, K, T>,
+    other: &'a KeyHashSet<T, K, F, S>,
+}
+
+pub struct Difference<'a, T, K: Hash, F, S> {
+    iter: hash_map::Iter<'a, K, T>,
+    other: &'a KeyHashSet<T, K, F, S>,
+}
+
+pub struct Union<'a, T, K: Hash, F, S> {
+    iter: Chain<hash_map::Values<'a, K, T>, Difference<'a, T, K, F, S>>,
+}
+
+pub struct SymmetricDifference<'a, T, K: Hash, F, S> {
+    left: Difference<'a, T, K, F, S>,
+    right: Difference<'a, T, K, F, S>,
+}
+
+impl<'a, T, K, F, S> Iterator for Intersection<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let other = self.other;
+
+        self.iter
+            .find(|(key, _)| other._value_map.contains_key(key))
+            .map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<'a, T, K, F, S> Iterator for Difference<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let other = self.other;
+
+        self.iter
+            .find(|(key, _)| !other._value_map.contains_key(key))
+            .map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<'a, T, K, F, S> Iterator for Union<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T, K, F, S> Iterator for SymmetricDifference<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.left.next().or_else(|| self.right.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (_, left) = self.left.size_hint();
+        let (_, right) = self.right.size_hint();
+
+        (0, left.zip(right).and_then(|(left, right)| left.checked_add(right)))
+    }
+}
+
+impl<'a, T, K, F, S> FusedIterator for Intersection<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {}
+impl<'a, T, K, F, S> FusedIterator for Difference<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {}
+impl<'a, T, K, F, S> FusedIterator for Union<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {}
+impl<'a, T, K, F, S> FusedIterator for SymmetricDifference<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher {}
+
+impl<'a, T, K: Hash, F, S> Clone for Intersection<'a, T, K, F, S> {
+    fn clone(&self) -> Self {
+        Intersection { iter: self.iter.clone(), other: self.other }
+    }
+}
+
+impl<'a, T, K: Hash, F, S> Clone for Difference<'a, T, K, F, S> {
+    fn clone(&self) -> Self {
+        Difference { iter: self.iter.clone(), other: self.other }
+    }
+}
+
+impl<'a, T, K: Hash, F, S> Clone for Union<'a, T, K, F, S> {
+    fn clone(&self) -> Self {
+        Union { iter: self.iter.clone() }
+    }
+}
+
+impl<'a, T, K: Hash, F, S> Clone for SymmetricDifference<'a, T, K, F, S> {
+    fn clone(&self) -> Self {
+        SymmetricDifference { left: self.left.clone(), right: self.right.clone() }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Entry
+
+/// A view into a single key of a `KeyHashSet`, built on `hash_map::Entry`.
+///
+/// `V` is the element the entry was made from by `KeyHashSet::entry`, inserted by `or_insert`
+/// and `VacantEntry::insert`, or `()` for `entry_by_key`. An element built by
+/// `or_insert_with` and the like must have the entry's key, and `and_modify` must not
+/// change it, both are checked with a panic before the set is left with a mismatched key.
+pub enum Entry<'a, T, K, F, V = T> {
+    Occupied(OccupiedEntry<'a, T, K, F, V>),
+    Vacant(VacantEntry<'a, T, K, F, V>),
+}
+
+pub struct OccupiedEntry<'a, T, K, F, V = T> {
+    get_key: &'a F,
+    entry: hash_map::OccupiedEntry<'a, K, T>,
+    value: V,
+}
+
+pub struct VacantEntry<'a, T, K, F, V = T> {
+    get_key: &'a F,
+    entry: hash_map::VacantEntry<'a, K, T>,
+    value: V,
+}
+
+pub(crate) fn assert_same_key<T, K, F>(get_key: &F, key: &K, value: &T) where K: Eq, F: GetKey<T, K> {
+    assert!(
+        get_key.get_key(value) == *key,
+        "the key of the element doesn't match the key of the entry"
+    );
+}
+
+impl<'a, T, K, F, V> Entry<'a, T, K, F, V> where K: Eq, F: GetKey<T, K> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert_with<G: FnOnce() -> T>(self, default: G) -> &'a T {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert_with(default),
+        }
+    }
+
+    /// Modify the element in place. If `f` changes its key, panicking or not,
+    /// the element is removed, then it panics.
+    pub fn and_modify<G: FnOnce(&mut T)>(self, f: G) -> Self {
+        match self {
+            Entry::Occupied(OccupiedEntry { get_key, entry, value }) => {
+                let mut check = ModifyCheck { get_key, entry: Some(entry) };
+                f(check.entry.as_mut().unwrap().get_mut());
+
+                let entry = check.entry.take().unwrap();
+                if get_key.get_key(entry.get()) != *entry.key() {
+                    entry.remove();
+                    panic!("the key of the element doesn't match the key of the entry");
+                }
+
+                Entry::Occupied(OccupiedEntry { get_key, entry, value })
+            },
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, T, K, F> Entry<'a, T, K, F> where K: Eq, F: GetKey<T, K> {
+    /// The element in the set, or the one the entry was made from, inserted.
+    pub fn or_insert(self) -> &'a T {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert(),
+        }
+    }
+}
+
+impl<'a, T, K, F, V> OccupiedEntry<'a, T, K, F, V> where K: Eq, F: GetKey<T, K> {
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    pub fn get(&self) -> &T {
+        self.entry.get()
+    }
+
+    pub fn into_ref(self) -> &'a T {
+        self.entry.into_mut()
+    }
+
+    /// Replace the element with the one built by `f`, of the same key, returning the old one.
+    pub fn replace_with<G: FnOnce() -> T>(mut self, f: G) -> T {
+        let value = f();
+        assert_same_key(self.get_key, self.key(), &value);
+
+        self.entry.insert(value)
+    }
+
+    pub fn remove(self) -> T {
+        self.entry.remove()
+    }
+}
+
+impl<'a, T, K, F> OccupiedEntry<'a, T, K, F> where K: Eq, F: GetKey<T, K> {
+    /// Replace the element with the one the entry was made from, returning the old one.
+    pub fn replace(mut self) -> T {
+        self.entry.insert(self.value)
+    }
+}
+
+impl<'a, T, K, F, V> VacantEntry<'a, T, K, F, V> where K: Eq, F: GetKey<T, K> {
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    pub fn into_key(self) -> K {
+        self.entry.into_key()
+    }
+
+    pub fn insert_with<G: FnOnce() -> T>(self, f: G) -> &'a T {
+        let value = f();
+        assert_same_key(self.get_key, self.key(), &value);
+
+        self.entry.insert(value)
+    }
+}
+
+impl<'a, T, K, F> VacantEntry<'a, T, K, F> where K: Eq, F: GetKey<T, K> {
+    /// Insert the element the entry was made from.
+    pub fn insert(self) -> &'a T {
+        self.entry.insert(self.value)
+    }
+}
+
+/// Removes the element of `and_modify` when dropped with a changed key, on unwinding included.
+struct ModifyCheck<'a, T, K, F> where K: Eq, F: GetKey<T, K> {
+    get_key: &'a F,
+    entry: Option<hash_map::OccupiedEntry<'a, K, T>>,
+}
+
+impl<T, K, F> Drop for ModifyCheck<'_, T, K, F> where K: Eq, F: GetKey<T, K> {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            if self.get_key.get_key(entry.get()) != *entry.key() {
+                entry.remove();
+            }
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// GuardMut
+
+/// What a `GuardMut` does when the key of its element has been changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyChangePolicy {
+    /// Move the element under its new key. A key held by another element is settled
+    /// by the set's `DuplicatePolicy` when that is `KeepLast` or `Merge`,
+    /// otherwise the element is treated as under `Reject`.
+    #[default]
+    Rehome,
+    /// Take the element out of the set, then panic.
+    Panic,
+    /// Take the element out of the set, `GuardMut::commit` hands it back
+    /// and dropping the guard drops it.
+    Reject,
+}
+
+/// The element taken out by `GuardMut::commit` for a key change it couldn't apply.
+pub struct KeyChanged<T> {
+    value: T,
+}
+
+impl<T> KeyChanged<T> {
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> fmt::Debug for KeyChanged<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyChanged").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for KeyChanged<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the key of the element was changed through a GuardMut")
+    }
+}
+
+impl<T> Error for KeyChanged<T> {}
+
+/// Mutable access to an element of a `KeyHashSet`.
+///
+/// The element stays in the set while it is borrowed, each deref looks it up by its old key.
+/// On release (drop or `commit`) it is only moved if its key changed,
+/// as the set's `KeyChangePolicy` says. An element whose new key can't be applied
+/// is taken out, it is never left under a key it no longer has.
+///
+/// Leaking the guard skips the check, like leaking any other guard.
+pub struct GuardMut<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    set: &'a mut KeyHashSet<T, K, F, S>,
+    key: Option<K>,
+}
+
+impl<'a, T, K, F, S> GuardMut<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    /// Settle a changed key, returning the element displaced by a rehome,
+    /// or the element itself if the change couldn't be applied.
+    pub fn commit(mut self) -> Result<Option<T>, KeyChanged<T>> {
+        self.release().map_err(|value| KeyChanged { value })
+    }
+
+    fn release(&mut self) -> Result<Option<T>, T> {
+        let old_key = match self.key.take() {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let set = &mut *self.set;
+        let key = set.get_key.get_key(&set._value_map[&old_key]);
+
+        if key == old_key {
+            return Ok(None);
+        }
+
+        let value = set._value_map.remove(&old_key).unwrap();
+        let settles = match set._value_map.get(&key) {
+            None => true,
+            Some(_) => matches!(set.duplicate_policy, DuplicatePolicy::KeepLast | DuplicatePolicy::Merge(_)),
+        };
+
+        match set.key_change_policy {
+            KeyChangePolicy::Rehome if settles => {
+                match set._value_map.entry(key) {
+                    hash_map::Entry::Occupied(mut entry) => {
+                        let outcome = set.duplicate_policy.resolve(entry.get_mut(), value);
+
+                        if let InsertOutcome::Merged = outcome {
+                            if set.get_key.get_key(entry.get()) != *entry.key() {
+                                entry.remove();
+                                panic!("the key of the element doesn't match the key of the entry");
+                            }
+                        }
+                        Ok(outcome.into_displaced())
+                    },
+                    hash_map::Entry::Vacant(entry) => {
+                        entry.insert(value);
+                        Ok(None)
+                    },
+                }
+            },
+            KeyChangePolicy::Panic if !thread::panicking() => {
+                panic!("the key of the element was changed through a GuardMut");
+            },
+            _ => Err(value),
+        }
+    }
+}
+
+impl<'a, T, K, F, S> Deref for GuardMut<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.set._value_map[self.key.as_ref().unwrap()]
+    }
+}
+
+impl<'a, T, K, F, S> DerefMut for GuardMut<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.set._value_map.get_mut(self.key.as_ref().unwrap()).unwrap()
+    }
+}
+
+impl<'a, T, K, F, S> Drop for GuardMut<'a, T, K, F, S> where K: Eq + Hash, S: BuildHasher, F: GetKey<T, K> {
+    fn drop(&mut self) {
+        let _ = self.release();
     }
 }
 
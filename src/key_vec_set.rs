@@ -0,0 +1,635 @@
+use std::borrow::{ Borrow };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::ops::{ Bound, Index, RangeBounds };
+use std::slice;
+use std::vec;
+use std::mem;
+use std::fmt;
+
+use crate::key_btree_set::{ Side, merge_join };
+use crate::key_set::{
+    KeySet, GetKey, GetKeyType, Map2SetType, DuplicatePolicy, InsertOutcome, Bias, assert_same_key
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+// KeyVecSet
+
+/// A key set stored as a `Vec` of entries sorted by key, for sets built once
+/// and read many times.
+///
+/// Lookups are binary searches over contiguous memory and iteration is a slice
+/// walk in key order. Building or extending sorts and dedups in bulk, in
+/// O(n log n), while a single `insert` or `remove` shifts the tail, in O(n).
+pub struct KeyVecSet<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    duplicate_policy: DuplicatePolicy<T>,
+    _value_vec: Vec<(K, T)>,
+}
+
+impl<T, K> KeyVecSet<T, K> where K: Ord {
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+
+    pub fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
+        Self::from_intoiter_with(get_key, iter)
+    }
+}
+
+impl<T, K, F> KeyVecSet<T, K, F> where K: Ord, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F) -> Self {
+        KeyVecSet {
+            get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            _value_vec: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(get_key: F, capacity: usize) -> Self {
+        KeyVecSet {
+            get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            _value_vec: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.extend(iter);
+        this
+    }
+
+    pub fn from_intoiter_with_policy(
+        get_key: F,
+        policy: DuplicatePolicy<T>,
+        iter: impl IntoIterator<Item=T>
+    ) -> Self
+    {
+        let mut this = Self::with_get_key(get_key);
+        this.duplicate_policy = policy;
+        this.extend(iter);
+        this
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy<T> {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy<T>) {
+        self.duplicate_policy = policy;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self._value_vec.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self._value_vec.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self._value_vec.shrink_to_fit();
+    }
+
+    /// Empty set with the same key function and policy.
+    fn empty_like(&self) -> Self where F: Clone {
+        KeyVecSet {
+            get_key: self.get_key.clone(),
+            duplicate_policy: self.duplicate_policy,
+            _value_vec: Vec::new(),
+        }
+    }
+
+    /// Set with the same key function and policy over entries already sorted by key.
+    fn sorted_like(&self, entries: Vec<(K, T)>) -> Self where F: Clone {
+        let mut new_set = self.empty_like();
+        new_set._value_vec = entries;
+
+        new_set
+    }
+
+    fn insert_value(&mut self, value: T) -> InsertOutcome<T> {
+        let key = self.get_key.get_key(&value);
+
+        match self._value_vec.binary_search_by(|(k, _)| k.cmp(&key)) {
+            Ok(index) => {
+                let (key, existing) = &mut self._value_vec[index];
+                let outcome = self.duplicate_policy.resolve(existing, value);
+
+                if let InsertOutcome::Merged = outcome {
+                    assert_same_key(&self.get_key, key, existing);
+                }
+                outcome
+            },
+            Err(index) => {
+                self._value_vec.insert(index, (key, value));
+                InsertOutcome::Inserted
+            },
+        }
+    }
+
+    /// Bulk insert: key and stable sort the new elements on the side, settle each
+    /// duplicate with the policy in place, then merge the fresh keys in.
+    ///
+    /// The set is only appended to once every duplicate is settled, a panic in the
+    /// key function or a merge leaves it sorted. Elements refused under
+    /// `DuplicatePolicy::Reject` are handed back.
+    fn insert_all(&mut self, iter: impl IntoIterator<Item=T>) -> Vec<T> {
+        let get_key = &self.get_key;
+        let mut incoming: Vec<(K, T)> = iter.into_iter().map(|value| (get_key.get_key(&value), value)).collect();
+        incoming.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut fresh: Vec<(K, T)> = Vec::with_capacity(incoming.len());
+        let mut rejected = Vec::new();
+
+        for (key, value) in incoming {
+            let existing = match fresh.last_mut() {
+                Some((last_key, last)) if *last_key == key => Some(last),
+                _ => match self._value_vec.binary_search_by(|(k, _)| k.cmp(&key)) {
+                    Ok(index) => Some(&mut self._value_vec[index].1),
+                    Err(_) => None,
+                },
+            };
+
+            match existing {
+                Some(existing) => match self.duplicate_policy.resolve(existing, value) {
+                    InsertOutcome::Rejected(value) => rejected.push(value),
+                    InsertOutcome::Merged => assert_same_key(&self.get_key, &key, existing),
+                    _ => (),
+                },
+                None => fresh.push((key, value)),
+            }
+        }
+
+        if !fresh.is_empty() {
+            // the sort finds both sorted halves as runs and merges them in linear time
+            self._value_vec.append(&mut fresh);
+            self._value_vec.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+
+        rejected
+    }
+
+    /// Like `extend`, but the elements refused under `DuplicatePolicy::Reject`
+    /// are handed back, in key order, instead of dropped.
+    pub fn try_extend(&mut self, iter: impl IntoIterator<Item=T>) -> Result<(), Vec<T>> {
+        let rejected = self.insert_all(iter);
+
+        if rejected.is_empty() { Ok(()) } else { Err(rejected) }
+    }
+
+    /// The element kept under `bias` for a key held by both sides.
+    fn pick(&self, key: &K, left: &T, right: &T, bias: Bias<T>) -> T where T: Clone {
+        match bias {
+            Bias::Left => left.clone(),
+            Bias::Right => right.clone(),
+            Bias::Merge(merge) => {
+                let merged = merge(left, right);
+                assert_same_key(&self.get_key, key, &merged);
+
+                merged
+            },
+        }
+    }
+
+    fn keyed(&self, value: T) -> (K, T) {
+        (self.get_key.get_key(&value), value)
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> KeyVecSet<T, K, F> where K: Ord {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Ord {
+        self.search(key).is_ok()
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Ord {
+        self.search(key).ok().map(|index| &self._value_vec[index].1)
+    }
+
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Ord {
+        self.take_by_key(key).is_some()
+    }
+
+    pub fn take_by_key<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Ord {
+        let index = self.search(key).ok()?;
+
+        Some(self._value_vec.remove(index).1)
+    }
+
+    /// Position of `key` in key order, or where it would be inserted.
+    pub fn rank<Q>(&self, key: &Q) -> Result<usize, usize> where K: Borrow<Q>, Q: ?Sized + Ord {
+        self.search(key)
+    }
+
+    /// Elements with a key in `range`, in key order.
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, T, K> where K: Borrow<Q>, Q: ?Sized + Ord, R: RangeBounds<Q> {
+        let start = match range.start_bound() {
+            Bound::Included(q) => self._value_vec.partition_point(|(k, _)| k.borrow() < q),
+            Bound::Excluded(q) => self._value_vec.partition_point(|(k, _)| k.borrow() <= q),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(q) => self._value_vec.partition_point(|(k, _)| k.borrow() <= q),
+            Bound::Excluded(q) => self._value_vec.partition_point(|(k, _)| k.borrow() < q),
+            Bound::Unbounded => self._value_vec.len(),
+        };
+
+        Iter {
+            iter: self._value_vec[start..end.max(start)].iter(),
+        }
+    }
+
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize> where K: Borrow<Q>, Q: ?Sized + Ord {
+        self._value_vec.binary_search_by(|(k, _)| k.borrow().cmp(key))
+    }
+}
+
+/// Index based access, in key order.
+impl<T, K, F> KeyVecSet<T, K, F> {
+    pub fn get_index(&self, index: usize) -> Option<&T> {
+        self._value_vec.get(index).map(|(_, v)| v)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self._value_vec.first().map(|(_, v)| v)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self._value_vec.last().map(|(_, v)| v)
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self._value_vec.pop().map(|(_, v)| v)
+    }
+
+    pub fn keys(&self) -> Keys<'_, T, K> {
+        Keys {
+            iter: self._value_vec.iter(),
+        }
+    }
+
+    /// The entries as a slice sorted by key.
+    pub fn as_slice(&self) -> &[(K, T)] {
+        &self._value_vec
+    }
+}
+
+impl<T, K, F> KeySet<T, K> for KeyVecSet<T, K, F> where K: Ord, F: GetKey<T, K> {
+    type KeyFn = F;
+    type Iter<'a> = Iter<'a, T, K> where Self: 'a, T: 'a;
+    type Drain<'a> = Drain<'a, T, K> where Self: 'a, T: 'a;
+
+    fn with_get_key(get_key: F) -> Self {
+        KeyVecSet::with_get_key(get_key)
+    }
+
+    fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        KeyVecSet::from_intoiter_with(get_key, iter)
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        self.insert_value(value)
+    }
+
+    fn try_insert(&mut self, value: T) -> Result<&T, T> {
+        let key = self.get_key.get_key(&value);
+
+        match self._value_vec.binary_search_by(|(k, _)| k.cmp(&key)) {
+            Ok(_) => Err(value),
+            Err(index) => {
+                self._value_vec.insert(index, (key, value));
+                Ok(&self._value_vec[index].1)
+            },
+        }
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.search(&self.get_key.get_key(value)).is_ok()
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        self.remove_by_key(&self.get_key.get_key(value))
+    }
+
+    fn take(&mut self, value: &T) -> Option<T> {
+        self.take_by_key(&self.get_key.get_key(value))
+    }
+
+    fn get(&self, value: &T) -> Option<&T> {
+        self.get_by_key(&self.get_key.get_key(value))
+    }
+
+    fn len(&self) -> usize {
+        self._value_vec.len()
+    }
+
+    fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            iter: self._value_vec.iter(),
+        }
+    }
+
+    fn retain<P: FnMut(&T) -> bool>(&mut self, mut pred: P) {
+        self._value_vec.retain(|(_, v)| pred(v));
+    }
+
+    fn drain(&mut self) -> Drain<'_, T, K> {
+        Drain {
+            iter: self._value_vec.drain(..),
+        }
+    }
+
+    fn extract_if<'a, P: FnMut(&T) -> bool + 'a>(&'a mut self, pred: P) -> impl Iterator<Item=T> + 'a {
+        ExtractIf {
+            vec: &mut self._value_vec,
+            index: 0,
+            pred,
+        }
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        KeyVecSet::contains_key(self, key)
+    }
+
+    fn get_by_key(&self, key: &K) -> Option<&T> {
+        KeyVecSet::get_by_key(self, key)
+    }
+
+    fn remove_by_key(&mut self, key: &K) -> bool {
+        KeyVecSet::remove_by_key(self, key)
+    }
+
+    fn take_by_key(&mut self, key: &K) -> Option<T> {
+        KeyVecSet::take_by_key(self, key)
+    }
+
+    fn intersection_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self.entries(), other.entries())
+            .filter_map(|side| match side {
+                Side::Both((key, v), (_, other_v)) => Some(self.keyed(self.pick(key, v, other_v, bias))),
+                _ => None,
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn union_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self.entries(), other.entries())
+            .map(|side| match side {
+                Side::Left((_, v)) | Side::Right((_, v)) => self.keyed(v.clone()),
+                Side::Both((key, v), (_, other_v)) => self.keyed(self.pick(key, v, other_v, bias)),
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self.entries(), other.entries())
+            .filter_map(|side| match side {
+                Side::Left((_, v)) => Some(self.keyed(v.clone())),
+                _ => None,
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let entries = merge_join::<K, _, _>(self.entries(), other.entries())
+            .filter_map(|side| match side {
+                Side::Left((_, v)) | Side::Right((_, v)) => Some(self.keyed(v.clone())),
+                Side::Both(..) => None,
+            })
+            .collect();
+
+        self.sorted_like(entries)
+    }
+
+    fn union_with(&mut self, other: Self) {
+        let entries = mem::take(&mut self._value_vec);
+
+        self._value_vec = merge_join::<K, _, _>(entries.into_iter(), other._value_vec.into_iter())
+            .map(|side| match side {
+                Side::Left(entry) | Side::Right(entry) | Side::Both(entry, _) => entry,
+            })
+            .collect();
+    }
+
+    fn retain_intersection(&mut self, other: &Self) {
+        let entries = mem::take(&mut self._value_vec);
+
+        self._value_vec = merge_join::<K, _, _>(entries.into_iter(), other.entries())
+            .filter_map(|side| match side {
+                Side::Both(entry, _) => Some(entry),
+                _ => None,
+            })
+            .collect();
+    }
+
+    fn subtract(&mut self, other: &Self) {
+        let entries = mem::take(&mut self._value_vec);
+
+        self._value_vec = merge_join::<K, _, _>(entries.into_iter(), other.entries())
+            .filter_map(|side| match side {
+                Side::Left(entry) => Some(entry),
+                _ => None,
+            })
+            .collect();
+    }
+
+    fn symmetric_difference_with(&mut self, other: Self) {
+        let entries = mem::take(&mut self._value_vec);
+
+        self._value_vec = merge_join::<K, _, _>(entries.into_iter(), other._value_vec.into_iter())
+            .filter_map(|side| match side {
+                Side::Left(entry) | Side::Right(entry) => Some(entry),
+                Side::Both(..) => None,
+            })
+            .collect();
+    }
+}
+
+impl<T, K, F> KeyVecSet<T, K, F> {
+    /// The entries as `(&K, &T)`, the shape `merge_join` walks.
+    fn entries(&self) -> impl Iterator<Item=(&K, &T)> {
+        self._value_vec.iter().map(|(k, v)| (k, v))
+    }
+}
+
+/// IntoIterator for KeyVecSet
+impl<T, K, F> IntoIterator for KeyVecSet<T, K, F> {
+    type Item = T;
+    type IntoIter = std::iter::Map<vec::IntoIter<(K, T)>, Map2SetType<T, K>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self._value_vec.into_iter().map(|(_, v)| v)
+    }
+}
+
+impl<'a, T, K, F> IntoIterator for &'a KeyVecSet<T, K, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Iter<'a, T, K> {
+        Iter {
+            iter: self._value_vec.iter(),
+        }
+    }
+}
+
+/// Index for KeyVecSet, panics when out of bounds
+impl<T, K, F> Index<usize> for KeyVecSet<T, K, F> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self._value_vec[index].1
+    }
+}
+
+/// PartialEq for KeyVecSet
+impl<T, K, F> PartialEq for KeyVecSet<T, K, F> where K: Ord {
+    fn eq(&self, other: &Self) -> bool {
+        self._value_vec.len() == other._value_vec.len()
+        && self.keys().eq(other.keys())
+    }
+}
+
+/// Debug for KeyVecSet
+impl<T, K, F> fmt::Debug for KeyVecSet<T, K, F> where T: fmt::Debug, K: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyVecSet")
+         .field("_value_vec", &self._value_vec)
+         .finish()
+    }
+}
+
+/// Extend for KeyVecSet, sorts once for the whole batch
+///
+/// Elements refused under `DuplicatePolicy::Reject` are dropped.
+impl<T, K, F> Extend<T> for KeyVecSet<T, K, F> where K: Ord, F: GetKey<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.insert_all(iter);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+pub struct Iter<'a, T, K> {
+    iter: slice::Iter<'a, (K, T)>,
+}
+
+impl<'a, T, K> Iterator for Iter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> DoubleEndedIterator for Iter<'_, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<T, K> ExactSizeIterator for Iter<'_, T, K> {}
+impl<T, K> FusedIterator for Iter<'_, T, K> {}
+
+impl<T, K> Clone for Iter<'_, T, K> {
+    fn clone(&self) -> Self {
+        Iter { iter: self.iter.clone() }
+    }
+}
+
+pub struct Keys<'a, T, K> {
+    iter: slice::Iter<'a, (K, T)>,
+}
+
+impl<'a, T, K> Iterator for Keys<'a, T, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.iter.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> DoubleEndedIterator for Keys<'_, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<T, K> ExactSizeIterator for Keys<'_, T, K> {}
+impl<T, K> FusedIterator for Keys<'_, T, K> {}
+
+impl<T, K> Clone for Keys<'_, T, K> {
+    fn clone(&self) -> Self {
+        Keys { iter: self.iter.clone() }
+    }
+}
+
+/// Draining iterator of `KeyVecSet`, in key order
+pub struct Drain<'a, T, K> {
+    iter: vec::Drain<'a, (K, T)>,
+}
+
+impl<T, K> Iterator for Drain<'_, T, K> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Drain<'_, T, K> {}
+impl<T, K> FusedIterator for Drain<'_, T, K> {}
+
+/// Iterator of `KeyVecSet::extract_if`, in key order.
+///
+/// The entries are visited in place by index, each match is shifted out of the
+/// vector. Entries not visited yet stay in the set when the iterator is dropped
+/// or leaked.
+pub struct ExtractIf<'a, T, K, P> {
+    vec: &'a mut Vec<(K, T)>,
+    index: usize,
+    pred: P,
+}
+
+impl<T, K, P> Iterator for ExtractIf<'_, T, K, P> where P: FnMut(&T) -> bool {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while let Some((_, v)) = self.vec.get(self.index) {
+            if (self.pred)(v) {
+                return Some(self.vec.remove(self.index).1);
+            }
+            self.index += 1;
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.vec.len() - self.index))
+    }
+}
+
+impl<T, K, P> FusedIterator for ExtractIf<'_, T, K, P> where P: FnMut(&T) -> bool {}
@@ -1,5 +1,50 @@
 mod key_set;
+mod slab;
+mod key_index_set;
+mod key_btree_set;
+mod key_multi_set;
+mod multi_key_set;
+mod key_bi_set;
+mod concurrent_key_set;
+mod persistent_key_set;
+mod lru_key_set;
+mod ttl_key_set;
+mod key_filter;
+mod small_key_set;
+mod key_vec_set;
 
 pub use crate::key_set::{
-    KeySet, GetKeyType, KeyHashSet, debug_key
+    KeySet, GetKeyType, DuplicatePolicy, InsertOutcome, Bias, GetKey, BoxedGetKey, SharedGetKey, KeyHashSet, debug_key,
+    Entry, OccupiedEntry, VacantEntry,
+    GuardMut, KeyChangePolicy, KeyChanged,
+    Iter, Keys, IterWithKeys, Drain, ExtractIf,
+    Intersection, Union, Difference, SymmetricDifference
+};
+
+pub use crate::slab::Iter as SlabIter;
+
+pub use crate::key_index_set::{
+    KeyIndexSet,
+    Iter as IndexIter, Drain as IndexDrain, ExtractIf as IndexExtractIf
+};
+pub use crate::key_btree_set::{
+    KeyBTreeSet,
+    Iter as BTreeIter, Range as BTreeRange, Drain as BTreeDrain, ExtractIf as BTreeExtractIf
+};
+pub use crate::key_multi_set::{ KeyMultiSet, Iter as MultiIter, Groups };
+pub use crate::multi_key_set::{ MultiKeySet, IndexHandle, UniqueViolation, GetAll };
+pub use crate::key_bi_set::{ KeyBiSet, BiCollision };
+pub use crate::concurrent_key_set::{ ConcurrentKeySet, Snapshot, IntoIter as ConcurrentIntoIter };
+pub use crate::persistent_key_set::{ PersistentKeySet, Iter as PersistentIter, Keys as PersistentKeys };
+pub use crate::lru_key_set::{ LruKeySet, Evicted, Iter as LruIter };
+pub use crate::ttl_key_set::{ TtlKeySet, Clock, SystemClock, ManualClock, Iter as TtlIter };
+pub use crate::key_filter::{ KeyBloomFilter, KeyCuckooFilter };
+pub use crate::small_key_set::{
+    SmallKeySet,
+    Iter as SmallIter, IterWithKeys as SmallIterWithKeys, IntoIter as SmallIntoIter,
+    Drain as SmallDrain, ExtractIf as SmallExtractIf
+};
+pub use crate::key_vec_set::{
+    KeyVecSet,
+    Iter as VecIter, Keys as VecKeys, Drain as VecDrain, ExtractIf as VecExtractIf
 };
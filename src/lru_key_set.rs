@@ -0,0 +1,365 @@
+use std::borrow::{ Borrow };
+use std::collections::{ HashMap };
+use std::hash::{ Hash };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::mem;
+use std::fmt;
+
+use crate::key_set::{ GetKey, GetKeyType };
+use crate::slab::{ Slab };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// LruKeySet
+
+/// A key set holding at most `capacity` elements, the least recently used one
+/// is evicted to make room for a new key.
+///
+/// `insert` always refreshes the element, `get` and `contains` do too unless
+/// `refresh_on_read` is turned off, the `peek` methods never do.
+///
+/// An element evicted for room goes to the `on_evict` callback when there is
+/// one, else it's handed back by `insert` as `Evicted::Overflow`.
+pub struct LruKeySet<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    capacity: usize,
+    refresh_on_read: bool,
+    on_evict: Option<Box<dyn FnMut(T) + Send>>,
+    nodes: Slab<Node<T>>,
+    /// most recently used
+    head: usize,
+    /// least recently used
+    tail: usize,
+    _index_map: HashMap<K, usize>,
+}
+
+const NIL: usize = usize::MAX;
+
+struct Node<T> {
+    value: T,
+    prev: usize,
+    next: usize,
+}
+
+/// An element dropped from a `LruKeySet` by `insert`.
+#[derive(Debug, PartialEq)]
+pub enum Evicted<T> {
+    /// The element with the same key, replaced by the inserted one.
+    Replaced(T),
+    /// The least recently used element, evicted to stay within the capacity.
+    Overflow(T),
+}
+
+impl<T> Evicted<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            Evicted::Replaced(value) | Evicted::Overflow(value) => value,
+        }
+    }
+}
+
+impl<T, K> LruKeySet<T, K> where K: Eq + Hash {
+    pub fn new(get_key: GetKeyType<T, K>, capacity: usize) -> Self {
+        Self::with_get_key(get_key, capacity)
+    }
+}
+
+impl<T, K, F> LruKeySet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    /// Panics if `capacity` is zero.
+    pub fn with_get_key(get_key: F, capacity: usize) -> Self {
+        assert!(capacity > 0, "a LruKeySet needs a capacity of at least one");
+
+        LruKeySet {
+            get_key,
+            capacity,
+            refresh_on_read: true,
+            on_evict: None,
+            nodes: Slab::with_capacity(capacity),
+            head: NIL,
+            tail: NIL,
+            _index_map: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Whether `get` and `contains` refresh the element, on by default.
+    pub fn with_refresh_on_read(mut self, refresh_on_read: bool) -> Self {
+        self.refresh_on_read = refresh_on_read;
+        self
+    }
+
+    /// Hand the elements evicted for room to `on_evict` instead of returning them.
+    ///
+    /// The callback must be `Send` so the set can still move between threads,
+    /// e.g. behind a `Mutex`.
+    pub fn with_on_evict(mut self, on_evict: impl FnMut(T) + Send + 'static) -> Self {
+        self.on_evict = Some(Box::new(on_evict));
+        self
+    }
+
+    /// Insert `value` as the most recently used element.
+    pub fn insert(&mut self, value: T) -> Option<Evicted<T>> {
+        let key = self.get_key.get_key(&value);
+
+        if let Some(&id) = self._index_map.get(&key) {
+            self.move_to_front(id);
+            let old = mem::replace(&mut self.node_mut(id).value, value);
+
+            return Some(Evicted::Replaced(old));
+        }
+
+        let evicted = if self._index_map.len() == self.capacity { self.evict() } else { None };
+
+        let id = self.push_front(value);
+        self._index_map.insert(key, id);
+
+        evicted.map(Evicted::Overflow)
+    }
+
+    pub fn contains(&mut self, value: &T) -> bool {
+        let key = self.get_key.get_key(value);
+        self.contains_key(&key)
+    }
+
+    pub fn get(&mut self, value: &T) -> Option<&T> {
+        let key = self.get_key.get_key(value);
+        self.get_by_key(&key)
+    }
+
+    pub fn peek(&self, value: &T) -> Option<&T> {
+        self.peek_by_key(&self.get_key.get_key(value))
+    }
+
+    pub fn remove(&mut self, value: &T) -> bool {
+        let key = self.get_key.get_key(value);
+        self.take_by_key(&key).is_some()
+    }
+
+    /// Change the capacity, evicting the least recently used elements over it.
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<T> {
+        assert!(capacity > 0, "a LruKeySet needs a capacity of at least one");
+        self.capacity = capacity;
+
+        let mut evicted = vec![];
+        while self._index_map.len() > capacity {
+            evicted.extend(self.evict());
+        }
+
+        evicted
+    }
+
+    /// Remove the least recently used element, it's not passed to `on_evict`.
+    pub fn pop_lru(&mut self) -> Option<T> {
+        if self.tail == NIL {
+            return None;
+        }
+        let value = self.unlink(self.tail);
+        self._index_map.remove(&self.get_key.get_key(&value));
+
+        Some(value)
+    }
+
+    /// Evict the least recently used element, `None` if `on_evict` took it.
+    fn evict(&mut self) -> Option<T> {
+        let value = self.pop_lru()?;
+
+        match self.on_evict.as_mut() {
+            Some(on_evict) => {
+                on_evict(value);
+                None
+            },
+            None => Some(value),
+        }
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> LruKeySet<T, K, F> where K: Eq + Hash {
+    pub fn contains_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.get_by_key(key).is_some()
+    }
+
+    pub fn get_by_key<Q>(&mut self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let id = *self._index_map.get(key)?;
+
+        if self.refresh_on_read {
+            self.move_to_front(id);
+        }
+
+        Some(&self.node(id).value)
+    }
+
+    pub fn peek_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self._index_map.get(key).map(|&id| &self.node(id).value)
+    }
+
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.take_by_key(key).is_some()
+    }
+
+    pub fn take_by_key<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let id = self._index_map.remove(key)?;
+
+        Some(self.unlink(id))
+    }
+
+    pub fn clear(&mut self) {
+        self._index_map.clear();
+        self.nodes.clear();
+        self.head = NIL;
+        self.tail = NIL;
+    }
+}
+
+impl<T, K, F> LruKeySet<T, K, F> {
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn refresh_on_read(&self) -> bool {
+        self.refresh_on_read
+    }
+
+    /// The element `pop_lru` would remove.
+    pub fn peek_lru(&self) -> Option<&T> {
+        (self.tail != NIL).then(|| &self.node(self.tail).value)
+    }
+
+    /// Iterate from the most to the least recently used element.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            nodes: &self.nodes,
+            next: self.head,
+            len: self.len(),
+        }
+    }
+
+    fn node(&self, id: usize) -> &Node<T> {
+        self.nodes.get(id)
+    }
+
+    fn node_mut(&mut self, id: usize) -> &mut Node<T> {
+        self.nodes.get_mut(id)
+    }
+
+    fn push_front(&mut self, value: T) -> usize {
+        let id = self.nodes.insert(Node { value, prev: NIL, next: self.head });
+
+        match self.head {
+            NIL => self.tail = id,
+            head => self.node_mut(head).prev = id,
+        }
+        self.head = id;
+
+        id
+    }
+
+    fn detach(&mut self, id: usize) {
+        let (prev, next) = {
+            let node = self.node(id);
+            (node.prev, node.next)
+        };
+
+        match prev {
+            NIL => self.head = next,
+            prev => self.node_mut(prev).next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.node_mut(next).prev = prev,
+        }
+    }
+
+    fn unlink(&mut self, id: usize) -> T {
+        self.detach(id);
+
+        self.nodes.remove(id).value
+    }
+
+    fn move_to_front(&mut self, id: usize) {
+        if self.head == id {
+            return;
+        }
+        self.detach(id);
+
+        let head = self.head;
+        {
+            let node = self.node_mut(id);
+            node.prev = NIL;
+            node.next = head;
+        }
+
+        match head {
+            NIL => self.tail = id,
+            head => self.node_mut(head).prev = id,
+        }
+        self.head = id;
+    }
+}
+
+impl<'a, T, K, F> IntoIterator for &'a LruKeySet<T, K, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Debug for LruKeySet, from the most recently used element
+impl<T, K, F> fmt::Debug for LruKeySet<T, K, F> where T: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+pub struct Iter<'a, T> {
+    nodes: &'a Slab<Node<T>>,
+    next: usize,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.next == NIL {
+            return None;
+        }
+        let node = self.nodes.get(self.next);
+        self.next = node.next;
+        self.len -= 1;
+
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Iter {
+            nodes: self.nodes,
+            next: self.next,
+            len: self.len,
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use std::any::{ Any };
+use std::borrow::{ Borrow };
+use std::collections::{ HashMap };
+use std::error::Error;
+use std::hash::{ Hash };
+use std::marker::PhantomData;
+use std::iter::{ FusedIterator };
+use std::slice;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::fmt;
+
+use crate::key_set::{ BoxedGetKey };
+use crate::slab::{ Slab, Iter };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// MultiKeySet
+
+/// A collection storing each element once, looked up through any number of
+/// indexes, each with its own key function.
+///
+/// A unique index holds at most one element per key, a non-unique one groups
+/// the elements in insertion order. Removing through any index removes the
+/// element from all of them.
+///
+/// Adding an index returns an `IndexHandle` typed by its key, lookups go
+/// through the handle so the key type is checked at compile time.
+pub struct MultiKeySet<T> {
+    id: u64,
+    slab: Slab<T>,
+    indexes: Vec<(String, Box<dyn ErasedIndex<T>>)>,
+}
+
+/// Tells the sets apart, so a handle can't be used with a set it doesn't belong to.
+static NEXT_SET_ID: AtomicU64 = AtomicU64::new(0);
+
+impl<T: 'static> MultiKeySet<T> {
+    pub fn new() -> Self {
+        MultiKeySet {
+            id: NEXT_SET_ID.fetch_add(1, Ordering::Relaxed),
+            slab: Slab::new(),
+            indexes: Vec::new(),
+        }
+    }
+
+    /// Add an index holding at most one element per key.
+    ///
+    /// Panics if `name` is taken or the elements already held have duplicate keys.
+    pub fn with_unique_index<K, G>(&mut self, name: &str, get_key: G) -> IndexHandle<K>
+    where K: Eq + Hash + 'static, G: Fn(&T) -> K + 'static
+    {
+        self.with_key_index(name, KeyIndex::new(Box::new(get_key), true))
+    }
+
+    /// Add an index grouping the elements sharing a key.
+    ///
+    /// Panics if `name` is taken.
+    pub fn with_index<K, G>(&mut self, name: &str, get_key: G) -> IndexHandle<K>
+    where K: Eq + Hash + 'static, G: Fn(&T) -> K + 'static
+    {
+        self.with_key_index(name, KeyIndex::new(Box::new(get_key), false))
+    }
+
+    fn with_key_index<K>(&mut self, name: &str, mut index: KeyIndex<T, K>) -> IndexHandle<K>
+    where K: Eq + Hash + 'static
+    {
+        assert!(self.indexes.iter().all(|(n, _)| n != name), "index {:?} is already defined", name);
+
+        for (id, value) in self.slab.slots().iter().enumerate() {
+            if let Some(value) = value {
+                assert!(!index.conflicts(value), "duplicate key for the unique index {:?}", name);
+                index.insert(id, value);
+            }
+        }
+        self.indexes.push((name.to_string(), Box::new(index)));
+
+        IndexHandle {
+            set_id: self.id,
+            pos: self.indexes.len() - 1,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Insert `value` into every index.
+    ///
+    /// The uniqueness of all the unique indexes is checked first, so a rejected
+    /// value leaves the set untouched.
+    pub fn insert(&mut self, value: T) -> Result<(), UniqueViolation<T>> {
+        if let Some((name, _)) = self.indexes.iter().find(|(_, index)| index.conflicts(&value)) {
+            return Err(UniqueViolation { index: name.clone(), value });
+        }
+
+        let id = self.slab.insert(value);
+
+        for (_, index) in self.indexes.iter_mut() {
+            index.insert(id, self.slab.get(id));
+        }
+
+        Ok(())
+    }
+
+    fn remove_id(&mut self, id: usize) -> T {
+        let value = self.slab.remove(id);
+
+        for (_, index) in self.indexes.iter_mut() {
+            index.remove(id, &value);
+        }
+
+        value
+    }
+}
+
+/// Lookups go through the handle of an index and accept any borrowed form of its key,
+/// e.g. `&str` for a `String` key. A handle of another set panics.
+impl<T: 'static> MultiKeySet<T> {
+    /// The first element of `key` in `index`.
+    pub fn get_by<K, Q>(&self, index: &IndexHandle<K>, key: &Q) -> Option<&T>
+    where K: Eq + Hash + Borrow<Q> + 'static, Q: ?Sized + Eq + Hash
+    {
+        self.ids_by(index, key).first().map(|&id| self.slab.get(id))
+    }
+
+    /// All the elements of `key` in `index`, in insertion order.
+    pub fn get_all_by<K, Q>(&self, index: &IndexHandle<K>, key: &Q) -> GetAll<'_, T>
+    where K: Eq + Hash + Borrow<Q> + 'static, Q: ?Sized + Eq + Hash
+    {
+        GetAll {
+            ids: self.ids_by(index, key).iter(),
+            slots: self.slab.slots(),
+        }
+    }
+
+    pub fn contains_key_by<K, Q>(&self, index: &IndexHandle<K>, key: &Q) -> bool
+    where K: Eq + Hash + Borrow<Q> + 'static, Q: ?Sized + Eq + Hash
+    {
+        !self.ids_by(index, key).is_empty()
+    }
+
+    pub fn count_by<K, Q>(&self, index: &IndexHandle<K>, key: &Q) -> usize
+    where K: Eq + Hash + Borrow<Q> + 'static, Q: ?Sized + Eq + Hash
+    {
+        self.ids_by(index, key).len()
+    }
+
+    /// Remove the first element of `key` in `index` from every index.
+    pub fn take_by<K, Q>(&mut self, index: &IndexHandle<K>, key: &Q) -> Option<T>
+    where K: Eq + Hash + Borrow<Q> + 'static, Q: ?Sized + Eq + Hash
+    {
+        let id = *self.ids_by(index, key).first()?;
+
+        Some(self.remove_id(id))
+    }
+
+    /// Remove all the elements of `key` in `index` from every index.
+    pub fn remove_all_by<K, Q>(&mut self, index: &IndexHandle<K>, key: &Q) -> Vec<T>
+    where K: Eq + Hash + Borrow<Q> + 'static, Q: ?Sized + Eq + Hash
+    {
+        let ids = self.ids_by(index, key).to_vec();
+
+        ids.into_iter().map(|id| self.remove_id(id)).collect()
+    }
+
+    fn ids_by<K, Q>(&self, index: &IndexHandle<K>, key: &Q) -> &[usize]
+    where K: Eq + Hash + Borrow<Q> + 'static, Q: ?Sized + Eq + Hash
+    {
+        assert!(index.set_id == self.id, "the index handle belongs to another MultiKeySet");
+
+        self.indexes[index.pos].1
+            .as_any()
+            .downcast_ref::<KeyIndex<T, K>>()
+            .expect("a handle has the key type of its index")
+            .ids(key)
+    }
+}
+
+impl<T> MultiKeySet<T> {
+    pub fn len(&self) -> usize {
+        self.slab.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn index_names(&self) -> impl Iterator<Item=&str> {
+        self.indexes.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.slab.iter()
+    }
+}
+
+impl<T: 'static> Default for MultiKeySet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a MultiKeySet<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Debug for MultiKeySet
+impl<T> fmt::Debug for MultiKeySet<T> where T: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiKeySet")
+         .field("indexes", &self.index_names().collect::<Vec<&str>>())
+         .field("elements", &self.iter().collect::<Vec<&T>>())
+         .finish()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// IndexHandle
+
+/// An index of a `MultiKeySet`, typed by its key, returned when the index is added.
+pub struct IndexHandle<K> {
+    set_id: u64,
+    pos: usize,
+    _marker: PhantomData<fn() -> K>,
+}
+
+impl<K> Clone for IndexHandle<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for IndexHandle<K> {}
+
+impl<K> fmt::Debug for IndexHandle<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IndexHandle")
+         .field("pos", &self.pos)
+         .finish_non_exhaustive()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Indexes
+
+/// An index with its key type erased, so a set holds indexes of any key type.
+trait ErasedIndex<T> {
+    /// Whether inserting `value` would break the uniqueness of the index.
+    fn conflicts(&self, value: &T) -> bool;
+    fn insert(&mut self, id: usize, value: &T);
+    fn remove(&mut self, id: usize, value: &T);
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Slot ids of the elements by key.
+struct KeyIndex<T, K> {
+    get_key: BoxedGetKey<T, K>,
+    unique: bool,
+    ids: HashMap<K, Vec<usize>>,
+}
+
+impl<T, K> KeyIndex<T, K> where K: Eq + Hash {
+    fn new(get_key: BoxedGetKey<T, K>, unique: bool) -> Self {
+        KeyIndex {
+            get_key,
+            unique,
+            ids: HashMap::new(),
+        }
+    }
+
+    fn ids<Q>(&self, key: &Q) -> &[usize] where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.ids.get(key).map_or(&[], Vec::as_slice)
+    }
+}
+
+impl<T, K> ErasedIndex<T> for KeyIndex<T, K> where T: 'static, K: Eq + Hash + 'static {
+    fn conflicts(&self, value: &T) -> bool {
+        self.unique && self.ids.contains_key(&(self.get_key)(value))
+    }
+
+    fn insert(&mut self, id: usize, value: &T) {
+        self.ids.entry((self.get_key)(value)).or_default().push(id);
+    }
+
+    fn remove(&mut self, id: usize, value: &T) {
+        let key = (self.get_key)(value);
+
+        if let Some(ids) = self.ids.get_mut(&key) {
+            ids.retain(|&x| x != id);
+
+            if ids.is_empty() {
+                self.ids.remove(&key);
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Errors
+
+/// A value rejected by `MultiKeySet::insert`, its key is already held by a unique index.
+pub struct UniqueViolation<T> {
+    index: String,
+    value: T,
+}
+
+impl<T> UniqueViolation<T> {
+    /// Name of the first unique index holding the key.
+    pub fn index(&self) -> &str {
+        &self.index
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> fmt::Debug for UniqueViolation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UniqueViolation")
+         .field("index", &self.index)
+         .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for UniqueViolation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the key is already held by the unique index {:?}", self.index)
+    }
+}
+
+impl<T> Error for UniqueViolation<T> {}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+/// Iterator of `MultiKeySet::get_all_by`
+pub struct GetAll<'a, T> {
+    ids: slice::Iter<'a, usize>,
+    slots: &'a [Option<T>],
+}
+
+impl<'a, T> Iterator for GetAll<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.ids.next().and_then(|&id| self.slots[id].as_ref())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for GetAll<'_, T> {}
+impl<T> FusedIterator for GetAll<'_, T> {}
+
+impl<T> Clone for GetAll<'_, T> {
+    fn clone(&self) -> Self {
+        GetAll {
+            ids: self.ids.clone(),
+            slots: self.slots,
+        }
+    }
+}
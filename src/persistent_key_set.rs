@@ -0,0 +1,674 @@
+use std::borrow::{ Borrow };
+use std::collections::hash_map::{ RandomState };
+use std::hash::{ BuildHasher, Hash };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::sync::{ Arc };
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::fmt;
+
+use crate::key_set::{ GetKey, GetKeyType, Bias, assert_same_key };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PersistentKeySet
+
+/// An immutable key set, `insert` and `remove` return a new set sharing all but
+/// the changed path with the old one.
+///
+/// It's a hash array mapped trie: every node branches on 5 bits of the key hash
+/// and is shared through an `Arc`, so `clone` is O(1) and a new version costs
+/// O(log n) nodes. The shape of the trie only depends on the keys it holds,
+/// which lets equality skip the subtrees two versions share.
+pub struct PersistentKeySet<T, K, F = GetKeyType<T, K>> {
+    get_key: F,
+    hasher: Arc<RandomState>,
+    root: Arc<Branch<K, T>>,
+    len: usize,
+}
+
+impl<T, K> PersistentKeySet<T, K> where K: Eq + Hash {
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+
+    pub fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
+        Self::from_intoiter_with(get_key, iter)
+    }
+}
+
+impl<T, K, F> PersistentKeySet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F) -> Self {
+        PersistentKeySet {
+            get_key,
+            hasher: Arc::new(RandomState::new()),
+            root: Arc::new(Branch::empty()),
+            len: 0,
+        }
+    }
+
+    pub fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.extend(iter);
+        this
+    }
+
+    /// A new set holding `value`, replacing the element of the same key.
+    pub fn insert(&self, value: T) -> Self where F: Clone {
+        let mut new_set = self.clone();
+        new_set.insert_mut(value);
+
+        new_set
+    }
+
+    /// A new set without the element with the key of `value`.
+    pub fn remove(&self, value: &T) -> Self where F: Clone {
+        self.remove_by_key(&self.get_key.get_key(value))
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.contains_key(&self.get_key.get_key(value))
+    }
+
+    pub fn get(&self, value: &T) -> Option<&T> {
+        self.get_by_key(&self.get_key.get_key(value))
+    }
+
+    /// Insert by copying the shared nodes on the path only, a set that isn't
+    /// shared is changed in place.
+    fn insert_mut(&mut self, value: T) {
+        let key = self.get_key.get_key(&value);
+        let leaf = Arc::new(Leaf { hash: self.hasher.hash_one(&key), key, value });
+
+        if Arc::make_mut(&mut self.root).insert(0, leaf).is_none() {
+            self.len += 1;
+        }
+    }
+}
+
+/// Set operations return a new version, built from a clone of `self` where
+/// they keep most of it. Leaves of a version of the same set are shared, not cloned.
+impl<T, K, F> PersistentKeySet<T, K, F> where T: Clone, K: Eq + Hash, F: GetKey<T, K> + Clone {
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.intersection_by(other, Bias::Left)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.union_by(other, Bias::Left)
+    }
+
+    pub fn intersection_by(&self, other: &Self, bias: Bias<T>) -> Self {
+        let mut new_set = self.empty_like();
+
+        for leaf in self.iter_leaves() {
+            if let Some(other_leaf) = other.find_leaf(self, leaf) {
+                match bias {
+                    Bias::Left => new_set.adopt(self, leaf),
+                    Bias::Right => new_set.adopt(other, other_leaf),
+                    Bias::Merge(_) => new_set.insert_mut(self.pick(leaf, other_leaf, bias)),
+                }
+            }
+        }
+
+        new_set
+    }
+
+    pub fn union_by(&self, other: &Self, bias: Bias<T>) -> Self {
+        let mut new_set = self.clone();
+
+        for other_leaf in other.iter_leaves() {
+            match (self.find_leaf(other, other_leaf), bias) {
+                (None, _) | (Some(_), Bias::Right) => new_set.adopt(other, other_leaf),
+                (Some(_), Bias::Left) => {},
+                (Some(leaf), Bias::Merge(_)) => new_set.insert_mut(self.pick(leaf, other_leaf, bias)),
+            }
+        }
+
+        new_set
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut new_set = self.clone();
+
+        for other_leaf in other.iter_leaves() {
+            new_set.remove_leaf(other, other_leaf);
+        }
+
+        new_set
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        let mut new_set = self.clone();
+
+        for other_leaf in other.iter_leaves() {
+            if !new_set.remove_leaf(other, other_leaf) {
+                new_set.adopt(other, other_leaf);
+            }
+        }
+
+        new_set
+    }
+
+    /// An empty version of the same set, hashing its keys the same way.
+    fn empty_like(&self) -> Self {
+        PersistentKeySet {
+            get_key: self.get_key.clone(),
+            hasher: self.hasher.clone(),
+            root: Arc::new(Branch::empty()),
+            len: 0,
+        }
+    }
+
+    /// Insert a `leaf` of the set `from`, shared when both sets are versions
+    /// of the same one.
+    fn adopt(&mut self, from: &Self, leaf: &Arc<Leaf<K, T>>) {
+        if !Arc::ptr_eq(&self.hasher, &from.hasher) {
+            return self.insert_mut(leaf.value.clone());
+        }
+
+        if Arc::make_mut(&mut self.root).insert(0, leaf.clone()).is_none() {
+            self.len += 1;
+        }
+    }
+
+    /// Remove the key of a `leaf` of the set `from`, returning whether it was held.
+    fn remove_leaf(&mut self, from: &Self, leaf: &Leaf<K, T>) -> bool {
+        let hash = self.hash_of(from, leaf);
+        if self.root.get(hash, &leaf.key).is_none() {
+            return false;
+        }
+
+        Arc::make_mut(&mut self.root).remove(0, hash, &leaf.key);
+        self.len -= 1;
+
+        true
+    }
+
+    /// The element kept under `bias` for a key held by both sides.
+    fn pick(&self, left: &Leaf<K, T>, right: &Leaf<K, T>, bias: Bias<T>) -> T {
+        match bias {
+            Bias::Left => left.value.clone(),
+            Bias::Right => right.value.clone(),
+            Bias::Merge(merge) => {
+                let merged = merge(&left.value, &right.value);
+                assert_same_key(&self.get_key, &left.key, &merged);
+
+                merged
+            },
+        }
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F> PersistentKeySet<T, K, F> where K: Eq + Hash {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.get_by_key(key).is_some()
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.root.get(self.hasher.hash_one(key), key).map(|leaf| &leaf.value)
+    }
+
+    /// A new set without the element of `key`.
+    pub fn remove_by_key<Q>(&self, key: &Q) -> Self where K: Borrow<Q>, Q: ?Sized + Eq + Hash, F: Clone {
+        let mut new_set = self.clone();
+        let hash = self.hasher.hash_one(key);
+
+        // checked first, a miss shouldn't copy the path
+        if self.root.get(hash, key).is_some() {
+            Arc::make_mut(&mut new_set.root).remove(0, hash, key);
+            new_set.len -= 1;
+        }
+
+        new_set
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len <= other.len && self.iter_leaves().all(|leaf| other.contains_leaf(self, leaf))
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.iter_leaves().all(|leaf| !other.contains_leaf(self, leaf))
+    }
+
+    /// Whether the key of a `leaf` of the set `from` is held.
+    fn contains_leaf(&self, from: &Self, leaf: &Leaf<K, T>) -> bool {
+        self.find_leaf(from, leaf).is_some()
+    }
+
+    /// The leaf holding the key of a `leaf` of the set `from`.
+    fn find_leaf(&self, from: &Self, leaf: &Leaf<K, T>) -> Option<&Arc<Leaf<K, T>>> {
+        self.root.get(self.hash_of(from, leaf), &leaf.key)
+    }
+
+    /// Hash of a `leaf` of the set `from`, reused when both sets are versions
+    /// of the same one.
+    fn hash_of(&self, from: &Self, leaf: &Leaf<K, T>) -> u64 {
+        if Arc::ptr_eq(&self.hasher, &from.hasher) { leaf.hash } else { self.hasher.hash_one(&leaf.key) }
+    }
+}
+
+impl<T, K, F> PersistentKeySet<T, K, F> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether both sets are the same version, sharing the whole trie.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            leaves: self.iter_leaves(),
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, T, K> {
+        Keys {
+            leaves: self.iter_leaves(),
+        }
+    }
+
+    fn iter_leaves(&self) -> Leaves<'_, K, T> {
+        Leaves {
+            stack: vec![self.root.children.iter()],
+            collision: [].iter(),
+            len: self.len,
+        }
+    }
+}
+
+/// Clone for PersistentKeySet, O(1)
+impl<T, K, F> Clone for PersistentKeySet<T, K, F> where F: Clone {
+    fn clone(&self) -> Self {
+        PersistentKeySet {
+            get_key: self.get_key.clone(),
+            hasher: self.hasher.clone(),
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<'a, T, K, F> IntoIterator for &'a PersistentKeySet<T, K, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Iter<'a, T, K> {
+        self.iter()
+    }
+}
+
+/// PartialEq for PersistentKeySet
+///
+/// Versions of the same set compare their tries, skipping the shared subtrees.
+impl<T, K, F> PartialEq for PersistentKeySet<T, K, F> where K: Eq + Hash {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+
+        if Arc::ptr_eq(&self.hasher, &other.hasher) {
+            self.root.trie_eq(&other.root)
+        }
+        else {
+            self.iter_leaves().all(|leaf| other.contains_leaf(self, leaf))
+        }
+    }
+}
+
+/// Debug for PersistentKeySet
+impl<T, K, F> fmt::Debug for PersistentKeySet<T, K, F> where T: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Extend for PersistentKeySet, in place
+impl<T, K, F> Extend<T> for PersistentKeySet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert_mut(value);
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Trie
+
+const BITS: u32 = 5;
+
+/// Slot of `hash` in a branch at depth `shift`.
+fn bit_of(hash: u64, shift: u32) -> u32 {
+    1 << ((hash >> shift) & ((1 << BITS) - 1))
+}
+
+struct Leaf<K, T> {
+    hash: u64,
+    key: K,
+    value: T,
+}
+
+/// Leaves whose keys have the same full hash.
+struct Collision<K, T> {
+    hash: u64,
+    leaves: Vec<Arc<Leaf<K, T>>>,
+}
+
+/// The children present, in slot order, flagged by `bitmap`.
+///
+/// Apart from the root, a branch holds at least two leaves below it, a single
+/// leaf or collision is moved up in place of the branch.
+struct Branch<K, T> {
+    bitmap: u32,
+    children: Vec<Child<K, T>>,
+}
+
+enum Child<K, T> {
+    Leaf(Arc<Leaf<K, T>>),
+    Collision(Arc<Collision<K, T>>),
+    Branch(Arc<Branch<K, T>>),
+}
+
+impl<K, T> Clone for Child<K, T> {
+    fn clone(&self) -> Self {
+        match self {
+            Child::Leaf(leaf) => Child::Leaf(leaf.clone()),
+            Child::Collision(collision) => Child::Collision(collision.clone()),
+            Child::Branch(branch) => Child::Branch(branch.clone()),
+        }
+    }
+}
+
+/// Clone the node only, the children are shared
+impl<K, T> Clone for Branch<K, T> {
+    fn clone(&self) -> Self {
+        Branch { bitmap: self.bitmap, children: self.children.clone() }
+    }
+}
+
+impl<K, T> Clone for Collision<K, T> {
+    fn clone(&self) -> Self {
+        Collision { hash: self.hash, leaves: self.leaves.clone() }
+    }
+}
+
+impl<K, T> Child<K, T> {
+    /// Hash of a leaf or a collision.
+    fn hash(&self) -> u64 {
+        match self {
+            Child::Leaf(leaf) => leaf.hash,
+            Child::Collision(collision) => collision.hash,
+            Child::Branch(_) => unreachable!("a branch has no single hash"),
+        }
+    }
+}
+
+impl<K, T> Branch<K, T> where K: Eq {
+    fn empty() -> Self {
+        Branch { bitmap: 0, children: Vec::new() }
+    }
+
+    fn index_of(&self, bit: u32) -> usize {
+        (self.bitmap & (bit - 1)).count_ones() as usize
+    }
+
+    fn get<Q>(&self, hash: u64, key: &Q) -> Option<&Arc<Leaf<K, T>>> where K: Borrow<Q>, Q: ?Sized + Eq {
+        let mut branch = self;
+        let mut shift = 0;
+
+        loop {
+            let bit = bit_of(hash, shift);
+            if branch.bitmap & bit == 0 {
+                return None;
+            }
+
+            match &branch.children[branch.index_of(bit)] {
+                Child::Leaf(leaf) => {
+                    return Some(leaf).filter(|leaf| leaf.hash == hash && leaf.key.borrow() == key);
+                },
+                Child::Collision(collision) => {
+                    if collision.hash != hash {
+                        return None;
+                    }
+                    return collision.leaves.iter().find(|leaf| leaf.key.borrow() == key);
+                },
+                Child::Branch(next) => {
+                    branch = next;
+                    shift += BITS;
+                },
+            }
+        }
+    }
+
+    /// Insert `leaf`, returning the leaf of the same key it replaced.
+    fn insert(&mut self, shift: u32, leaf: Arc<Leaf<K, T>>) -> Option<Arc<Leaf<K, T>>> {
+        let bit = bit_of(leaf.hash, shift);
+        let idx = self.index_of(bit);
+
+        if self.bitmap & bit == 0 {
+            self.bitmap |= bit;
+            self.children.insert(idx, Child::Leaf(leaf));
+            return None;
+        }
+
+        match &mut self.children[idx] {
+            Child::Branch(branch) => Arc::make_mut(branch).insert(shift + BITS, leaf),
+            Child::Leaf(old) if old.hash == leaf.hash && old.key == leaf.key => {
+                Some(mem::replace(old, leaf))
+            },
+            Child::Collision(collision) if collision.hash == leaf.hash => {
+                let collision = Arc::make_mut(collision);
+
+                match collision.leaves.iter_mut().find(|old| old.key == leaf.key) {
+                    Some(old) => Some(mem::replace(old, leaf)),
+                    None => {
+                        collision.leaves.push(leaf);
+                        None
+                    },
+                }
+            },
+            child => {
+                let old = child.clone();
+                *child = Self::split(old, leaf, shift + BITS);
+                None
+            },
+        }
+    }
+
+    /// The node holding `old`, a leaf or collision, and `leaf` from depth `shift`.
+    fn split(old: Child<K, T>, leaf: Arc<Leaf<K, T>>, shift: u32) -> Child<K, T> {
+        let old_hash = old.hash();
+
+        if old_hash == leaf.hash {
+            let old = match old {
+                Child::Leaf(old) => old,
+                _ => unreachable!("a collision of the same hash takes the leaf in"),
+            };
+            return Child::Collision(Arc::new(Collision { hash: old_hash, leaves: vec![old, leaf] }));
+        }
+
+        let (old_bit, bit) = (bit_of(old_hash, shift), bit_of(leaf.hash, shift));
+
+        let branch = if old_bit == bit {
+            Branch { bitmap: bit, children: vec![Self::split(old, leaf, shift + BITS)] }
+        }
+        else if old_bit < bit {
+            Branch { bitmap: old_bit | bit, children: vec![old, Child::Leaf(leaf)] }
+        }
+        else {
+            Branch { bitmap: old_bit | bit, children: vec![Child::Leaf(leaf), old] }
+        };
+
+        Child::Branch(Arc::new(branch))
+    }
+
+    /// Remove the leaf of `key`, which must be present.
+    fn remove<Q>(&mut self, shift: u32, hash: u64, key: &Q) where K: Borrow<Q>, Q: ?Sized + Eq {
+        let bit = bit_of(hash, shift);
+        let idx = self.index_of(bit);
+
+        let replacement = match &mut self.children[idx] {
+            Child::Leaf(_) => None,
+            Child::Collision(collision) => {
+                let collision = Arc::make_mut(collision);
+                collision.leaves.retain(|leaf| leaf.key.borrow() != key);
+
+                if collision.leaves.len() > 1 {
+                    return;
+                }
+                Some(Child::Leaf(collision.leaves[0].clone()))
+            },
+            Child::Branch(branch) => {
+                let branch = Arc::make_mut(branch);
+                branch.remove(shift + BITS, hash, key);
+
+                match branch.children.as_slice() {
+                    [Child::Branch(_)] => return,
+                    [child] => Some(child.clone()),
+                    _ => return,
+                }
+            },
+        };
+
+        match replacement {
+            Some(child) => self.children[idx] = child,
+            None => {
+                self.bitmap &= !bit;
+                self.children.remove(idx);
+            },
+        }
+    }
+
+    /// Equality of canonical tries, shared nodes are equal without a visit.
+    fn trie_eq(&self, other: &Self) -> bool {
+        if ptr::eq(self, other) {
+            return true;
+        }
+
+        self.bitmap == other.bitmap
+        && self.children.iter().zip(other.children.iter()).all(|pair| match pair {
+            (Child::Leaf(a), Child::Leaf(b)) => {
+                Arc::ptr_eq(a, b) || (a.hash == b.hash && a.key == b.key)
+            },
+            (Child::Collision(a), Child::Collision(b)) => {
+                Arc::ptr_eq(a, b)
+                || (a.hash == b.hash
+                    && a.leaves.len() == b.leaves.len()
+                    && a.leaves.iter().all(|x| b.leaves.iter().any(|y| x.key == y.key)))
+            },
+            (Child::Branch(a), Child::Branch(b)) => a.trie_eq(b),
+            _ => false,
+        })
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+/// Depth first walk of the leaves.
+struct Leaves<'a, K, T> {
+    stack: Vec<slice::Iter<'a, Child<K, T>>>,
+    collision: slice::Iter<'a, Arc<Leaf<K, T>>>,
+    len: usize,
+}
+
+impl<'a, K, T> Iterator for Leaves<'a, K, T> {
+    type Item = &'a Arc<Leaf<K, T>>;
+
+    fn next(&mut self) -> Option<&'a Arc<Leaf<K, T>>> {
+        loop {
+            if let Some(leaf) = self.collision.next() {
+                self.len -= 1;
+                return Some(leaf);
+            }
+
+            let top = self.stack.last_mut()?;
+            match top.next() {
+                None => { self.stack.pop(); },
+                Some(Child::Leaf(leaf)) => {
+                    self.len -= 1;
+                    return Some(leaf);
+                },
+                Some(Child::Collision(collision)) => self.collision = collision.leaves.iter(),
+                Some(Child::Branch(branch)) => self.stack.push(branch.children.iter()),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, T> Clone for Leaves<'_, K, T> {
+    fn clone(&self) -> Self {
+        Leaves {
+            stack: self.stack.clone(),
+            collision: self.collision.clone(),
+            len: self.len,
+        }
+    }
+}
+
+pub struct Iter<'a, T, K> {
+    leaves: Leaves<'a, K, T>,
+}
+
+impl<'a, T, K> Iterator for Iter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.leaves.next().map(|leaf| &leaf.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.leaves.size_hint()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Iter<'_, T, K> {}
+impl<T, K> FusedIterator for Iter<'_, T, K> {}
+
+impl<T, K> Clone for Iter<'_, T, K> {
+    fn clone(&self) -> Self {
+        Iter { leaves: self.leaves.clone() }
+    }
+}
+
+pub struct Keys<'a, T, K> {
+    leaves: Leaves<'a, K, T>,
+}
+
+impl<'a, T, K> Iterator for Keys<'a, T, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.leaves.next().map(|leaf| &leaf.key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.leaves.size_hint()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Keys<'_, T, K> {}
+impl<T, K> FusedIterator for Keys<'_, T, K> {}
+
+impl<T, K> Clone for Keys<'_, T, K> {
+    fn clone(&self) -> Self {
+        Keys { leaves: self.leaves.clone() }
+    }
+}
@@ -0,0 +1,115 @@
+use std::iter::{ FusedIterator };
+use std::slice;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Slab
+
+/// Elements stored under a stable slot id, the slots freed by removals are reused.
+///
+/// Backs the sets looking their elements up through more than one index, each
+/// index maps its keys to slot ids, and the sets linking their elements by id.
+pub(crate) struct Slab<T> {
+    slots: Vec<Option<T>>,
+    free: Vec<usize>,
+}
+
+impl<T> Slab<T> {
+    pub(crate) fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Slab {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+        }
+    }
+
+    /// Store `value`, returning its slot id.
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.slots[id] = Some(value);
+                id
+            },
+            None => {
+                self.slots.push(Some(value));
+                self.slots.len() - 1
+            },
+        }
+    }
+
+    pub(crate) fn remove(&mut self, id: usize) -> T {
+        let value = self.slots[id].take().expect("index refers to an empty slot");
+        self.free.push(id);
+
+        value
+    }
+
+    pub(crate) fn get(&self, id: usize) -> &T {
+        self.slots[id].as_ref().expect("index refers to an empty slot")
+    }
+
+    pub(crate) fn get_mut(&mut self, id: usize) -> &mut T {
+        self.slots[id].as_mut().expect("index refers to an empty slot")
+    }
+
+    /// Every slot by id, the empty ones included.
+    pub(crate) fn slots(&self) -> &[Option<T>] {
+        &self.slots
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.free.clear();
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            iter: self.slots.iter(),
+            len: self.len(),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+/// Iterator over the elements of a slab-backed set, in slot order.
+pub struct Iter<'a, T> {
+    iter: slice::Iter<'a, Option<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let v = self.iter.by_ref().flatten().next()?;
+        self.len -= 1;
+
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Iter {
+            iter: self.iter.clone(),
+            len: self.len,
+        }
+    }
+}
@@ -0,0 +1,760 @@
+use std::array;
+use std::borrow::{ Borrow };
+use std::collections::{ HashMap };
+use std::collections::hash_map;
+use std::hash::{ Hash };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::mem;
+use std::slice;
+use std::fmt;
+
+use crate::key_set;
+use crate::key_set::{
+    KeySet, GetKey, GetKeyType, DuplicatePolicy, InsertOutcome, Bias, assert_same_key
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SmallKeySet
+
+/// A key set holding up to `N` elements inline, found by comparing keys one by
+/// one, without allocating or hashing.
+///
+/// The `N + 1`-th key spills the elements to a `HashMap`, the set stays there
+/// until `shrink_to_fit` brings it back inline. The iteration order is
+/// unspecified either way, a removal inline moves the last element in its place.
+pub struct SmallKeySet<T, K, const N: usize, F = GetKeyType<T, K>> {
+    get_key: F,
+    duplicate_policy: DuplicatePolicy<T>,
+    storage: Storage<T, K, N>,
+}
+
+enum Storage<T, K, const N: usize> {
+    /// `slots[..len]` are all `Some`, the rest `None`.
+    Inline { slots: [Option<(K, T)>; N], len: usize },
+    Spilled(HashMap<K, T>),
+}
+
+fn empty_slots<T, K, const N: usize>() -> [Option<(K, T)>; N] {
+    array::from_fn(|_| None)
+}
+
+impl<T, K, const N: usize> SmallKeySet<T, K, N> where K: Eq + Hash {
+    pub fn new(get_key: GetKeyType<T, K>) -> Self {
+        Self::with_get_key(get_key)
+    }
+
+    pub fn from_intoiter(get_key: GetKeyType<T, K>, iter: impl IntoIterator<Item=T>) -> Self {
+        Self::from_intoiter_with(get_key, iter)
+    }
+}
+
+impl<T, K, const N: usize, F> SmallKeySet<T, K, N, F> where K: Eq + Hash, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F) -> Self {
+        SmallKeySet {
+            get_key,
+            duplicate_policy: DuplicatePolicy::default(),
+            storage: Storage::Inline { slots: empty_slots(), len: 0 },
+        }
+    }
+
+    pub fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        let mut this = Self::with_get_key(get_key);
+        this.insert_all(iter);
+        this
+    }
+
+    pub fn from_intoiter_with_policy(
+        get_key: F,
+        policy: DuplicatePolicy<T>,
+        iter: impl IntoIterator<Item=T>
+    ) -> Self
+    {
+        let mut this = Self::with_get_key(get_key);
+        this.duplicate_policy = policy;
+        this.insert_all(iter);
+        this
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy<T> {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy<T>) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Empty set with the same key function and policy.
+    fn empty_like(&self) -> Self where F: Clone {
+        SmallKeySet {
+            get_key: self.get_key.clone(),
+            duplicate_policy: self.duplicate_policy,
+            storage: Storage::Inline { slots: empty_slots(), len: 0 },
+        }
+    }
+
+    fn insert_value(&mut self, value: T) -> InsertOutcome<T> {
+        let key = self.get_key.get_key(&value);
+
+        match &mut self.storage {
+            Storage::Inline { slots, len } => {
+                match slots[..*len].iter_mut().flatten().find(|(k, _)| *k == key) {
+                    Some((k, existing)) => {
+                        let outcome = self.duplicate_policy.resolve(existing, value);
+
+                        if let InsertOutcome::Merged = outcome {
+                            assert_same_key(&self.get_key, k, existing);
+                        }
+                        outcome
+                    },
+                    None => {
+                        self.storage.push(key, value);
+                        InsertOutcome::Inserted
+                    },
+                }
+            },
+            Storage::Spilled(map) => {
+                match map.entry(key) {
+                    hash_map::Entry::Occupied(mut entry) => {
+                        let outcome = self.duplicate_policy.resolve(entry.get_mut(), value);
+
+                        if let InsertOutcome::Merged = outcome {
+                            assert_same_key(&self.get_key, entry.key(), entry.get());
+                        }
+                        outcome
+                    },
+                    hash_map::Entry::Vacant(entry) => {
+                        entry.insert(value);
+                        InsertOutcome::Inserted
+                    },
+                }
+            },
+        }
+    }
+
+    /// Bulk insert, the elements refused under `DuplicatePolicy::Reject` are handed back.
+    fn insert_all(&mut self, iter: impl IntoIterator<Item=T>) -> Vec<T> {
+        let mut rejected = Vec::new();
+
+        for value in iter {
+            if let InsertOutcome::Rejected(value) = self.insert_value(value) {
+                rejected.push(value);
+            }
+        }
+
+        rejected
+    }
+
+    /// Like `extend`, but the elements refused under `DuplicatePolicy::Reject`
+    /// are handed back, in iteration order, instead of dropped.
+    pub fn try_extend(&mut self, iter: impl IntoIterator<Item=T>) -> Result<(), Vec<T>> {
+        let rejected = self.insert_all(iter);
+
+        if rejected.is_empty() { Ok(()) } else { Err(rejected) }
+    }
+
+    /// The element kept under `bias` for a key held by both sides.
+    fn pick(&self, key: &K, left: &T, right: &T, bias: Bias<T>) -> T where T: Clone {
+        match bias {
+            Bias::Left => left.clone(),
+            Bias::Right => right.clone(),
+            Bias::Merge(merge) => {
+                let merged = merge(left, right);
+                assert_same_key(&self.get_key, key, &merged);
+
+                merged
+            },
+        }
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, const N: usize, F> SmallKeySet<T, K, N, F> where K: Eq + Hash {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.storage.get(key).is_some()
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.storage.get(key)
+    }
+
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.storage.remove(key).is_some()
+    }
+
+    pub fn take_by_key<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.storage.remove(key)
+    }
+
+    /// Move the elements back inline if they fit, else shrink the map.
+    pub fn shrink_to_fit(&mut self) {
+        if let Storage::Spilled(map) = &mut self.storage {
+            if map.len() > N {
+                map.shrink_to_fit();
+                return;
+            }
+
+            let mut slots = empty_slots();
+            let mut len = 0;
+            for pair in map.drain() {
+                slots[len] = Some(pair);
+                len += 1;
+            }
+            self.storage = Storage::Inline { slots, len };
+        }
+    }
+}
+
+impl<T, K, const N: usize, F> SmallKeySet<T, K, N, F> {
+    /// Number of elements held without spilling.
+    pub const fn inline_capacity() -> usize {
+        N
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.storage, Storage::Spilled(_))
+    }
+
+    pub fn iter_with_keys(&self) -> IterWithKeys<'_, T, K> {
+        self.storage.iter_with_keys()
+    }
+}
+
+impl<T, K, const N: usize> Storage<T, K, N> where K: Eq + Hash {
+    fn get<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match self {
+            Storage::Inline { slots, len } => {
+                slots[..*len].iter().flatten().find(|(k, _)| k.borrow() == key).map(|(_, v)| v)
+            },
+            Storage::Spilled(map) => map.get(key),
+        }
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match self {
+            Storage::Inline { slots, len } => {
+                let index = slots[..*len].iter().flatten().position(|(k, _)| k.borrow() == key)?;
+
+                Some(Self::swap_remove(slots, len, index))
+            },
+            Storage::Spilled(map) => map.remove(key),
+        }
+    }
+
+    /// Add a pair whose key isn't held, spilling if the slots are full.
+    fn push(&mut self, key: K, value: T) -> &T {
+        if let Storage::Inline { slots, len } = self {
+            if *len == N {
+                let mut map = HashMap::with_capacity(N * 2);
+                map.extend(IntoIterator::into_iter(mem::replace(slots, empty_slots())).flatten());
+
+                *self = Storage::Spilled(map);
+            }
+        }
+
+        match self {
+            Storage::Inline { slots, len } => {
+                *len += 1;
+                let (_, v) = slots[*len - 1].insert((key, value));
+
+                v
+            },
+            Storage::Spilled(map) => {
+                match map.entry(key) {
+                    hash_map::Entry::Vacant(entry) => entry.insert(value),
+                    hash_map::Entry::Occupied(_) => unreachable!("pushed a key already held"),
+                }
+            },
+        }
+    }
+
+    fn retain<P>(&mut self, mut pred: P) where P: FnMut(&K, &T) -> bool {
+        match self {
+            Storage::Inline { slots, len } => {
+                let mut index = 0;
+
+                while index < *len {
+                    let (k, v) = slots[index].as_ref().expect("inline slot below len is empty");
+
+                    if pred(k, v) {
+                        index += 1;
+                    }
+                    else {
+                        Self::swap_remove(slots, len, index);
+                    }
+                }
+            },
+            Storage::Spilled(map) => map.retain(|k, v| pred(k, v)),
+        }
+    }
+}
+
+impl<T, K, const N: usize> Storage<T, K, N> {
+    fn len(&self) -> usize {
+        match self {
+            Storage::Inline { len, .. } => *len,
+            Storage::Spilled(map) => map.len(),
+        }
+    }
+
+    /// Take the element at `index`, the last one moves in its place.
+    fn swap_remove(slots: &mut [Option<(K, T)>; N], len: &mut usize, index: usize) -> T {
+        *len -= 1;
+        slots.swap(index, *len);
+
+        slots[*len].take().expect("inline slot below len is empty").1
+    }
+
+    fn iter_with_keys(&self) -> IterWithKeys<'_, T, K> {
+        let inner = match self {
+            Storage::Inline { slots, len } => InnerIter::Inline(slots[..*len].iter()),
+            Storage::Spilled(map) => InnerIter::Spilled(map.iter()),
+        };
+
+        IterWithKeys { inner }
+    }
+
+    fn into_pairs(self) -> IntoPairs<T, K, N> {
+        match self {
+            Storage::Inline { slots, len } => IntoPairs::Inline(InlineIntoIter { iter: IntoIterator::into_iter(slots), len }),
+            Storage::Spilled(map) => IntoPairs::Spilled(map.into_iter()),
+        }
+    }
+}
+
+impl<T, K, const N: usize, F> KeySet<T, K> for SmallKeySet<T, K, N, F> where K: Eq + Hash, F: GetKey<T, K> {
+    type KeyFn = F;
+    type Iter<'a> = Iter<'a, T, K> where Self: 'a, T: 'a;
+    type Drain<'a> = Drain<'a, T, K, N> where Self: 'a, T: 'a;
+
+    fn with_get_key(get_key: F) -> Self {
+        SmallKeySet::with_get_key(get_key)
+    }
+
+    fn from_intoiter_with(get_key: F, iter: impl IntoIterator<Item=T>) -> Self {
+        SmallKeySet::from_intoiter_with(get_key, iter)
+    }
+
+    fn insert(&mut self, value: T) -> InsertOutcome<T> {
+        self.insert_value(value)
+    }
+
+    fn try_insert(&mut self, value: T) -> Result<&T, T> {
+        let key = self.get_key.get_key(&value);
+
+        if self.storage.get(&key).is_some() {
+            return Err(value);
+        }
+        Ok(self.storage.push(key, value))
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        self.storage.get(&self.get_key.get_key(value)).is_some()
+    }
+
+    fn remove(&mut self, value: &T) -> bool {
+        self.storage.remove(&self.get_key.get_key(value)).is_some()
+    }
+
+    fn take(&mut self, value: &T) -> Option<T> {
+        self.storage.remove(&self.get_key.get_key(value))
+    }
+
+    fn get(&self, value: &T) -> Option<&T> {
+        self.storage.get(&self.get_key.get_key(value))
+    }
+
+    fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            iter: self.storage.iter_with_keys(),
+        }
+    }
+
+    fn retain<P: FnMut(&T) -> bool>(&mut self, mut pred: P) {
+        self.storage.retain(|_, v| pred(v));
+    }
+
+    fn drain(&mut self) -> Drain<'_, T, K, N> {
+        let inner = match &mut self.storage {
+            Storage::Inline { slots, len } => {
+                let iter = IntoIterator::into_iter(mem::replace(slots, empty_slots()));
+
+                InnerDrain::Inline(InlineIntoIter { iter, len: mem::take(len) })
+            },
+            Storage::Spilled(map) => InnerDrain::Spilled(map.drain()),
+        };
+
+        Drain { inner }
+    }
+
+    fn extract_if<'a, P: FnMut(&T) -> bool + 'a>(&'a mut self, pred: P) -> impl Iterator<Item=T> + 'a {
+        let inner = match &mut self.storage {
+            Storage::Inline { slots, len } => InnerExtractIf::Inline { slots, len, index: 0, pred },
+            Storage::Spilled(map) => InnerExtractIf::Spilled(key_set::ExtractIf::new(map, &self.get_key, pred)),
+        };
+
+        ExtractIf { inner }
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        SmallKeySet::contains_key(self, key)
+    }
+
+    fn get_by_key(&self, key: &K) -> Option<&T> {
+        SmallKeySet::get_by_key(self, key)
+    }
+
+    fn remove_by_key(&mut self, key: &K) -> bool {
+        SmallKeySet::remove_by_key(self, key)
+    }
+
+    fn take_by_key(&mut self, key: &K) -> Option<T> {
+        SmallKeySet::take_by_key(self, key)
+    }
+
+    fn intersection_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.empty_like();
+
+        for (key, v) in self.iter_with_keys() {
+            if let Some(other_v) = other.storage.get(key) {
+                new_set.insert_value(self.pick(key, v, other_v, bias));
+            }
+        }
+
+        new_set
+    }
+
+    fn union_by<'a>(&'a self, other: &'a Self, bias: Bias<T>) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.empty_like();
+
+        for (key, v) in self.iter_with_keys() {
+            match other.storage.get(key) {
+                Some(other_v) => new_set.insert_value(self.pick(key, v, other_v, bias)),
+                None => new_set.insert_value(v.clone()),
+            };
+        }
+
+        for (key, v) in other.iter_with_keys() {
+            if self.storage.get(key).is_none() {
+                new_set.insert_value(v.clone());
+            }
+        }
+
+        new_set
+    }
+
+    fn difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.empty_like();
+
+        for (key, v) in self.iter_with_keys() {
+            if other.storage.get(key).is_none() {
+                new_set.insert_value(v.clone());
+            }
+        }
+
+        new_set
+    }
+
+    fn symmetric_difference<'a>(&'a self, other: &'a Self) -> Self where T: Clone, F: Clone {
+        let mut new_set = self.difference(other);
+
+        for (key, v) in other.iter_with_keys() {
+            if self.storage.get(key).is_none() {
+                new_set.insert_value(v.clone());
+            }
+        }
+
+        new_set
+    }
+
+    fn union_with(&mut self, other: Self) {
+        for (key, v) in other.storage.into_pairs() {
+            if self.storage.get(&key).is_none() {
+                self.storage.push(key, v);
+            }
+        }
+    }
+
+    fn retain_intersection(&mut self, other: &Self) {
+        self.storage.retain(|key, _| other.storage.get(key).is_some());
+    }
+
+    fn subtract(&mut self, other: &Self) {
+        self.storage.retain(|key, _| other.storage.get(key).is_none());
+    }
+
+    fn symmetric_difference_with(&mut self, other: Self) {
+        for (key, v) in other.storage.into_pairs() {
+            if self.storage.remove(&key).is_none() {
+                self.storage.push(key, v);
+            }
+        }
+    }
+}
+
+/// IntoIterator for SmallKeySet
+impl<T, K, const N: usize, F> IntoIterator for SmallKeySet<T, K, N, F> {
+    type Item = T;
+    type IntoIter = IntoIter<T, K, N>;
+
+    fn into_iter(self) -> IntoIter<T, K, N> {
+        IntoIter {
+            iter: self.storage.into_pairs(),
+        }
+    }
+}
+
+impl<'a, T, K, const N: usize, F> IntoIterator for &'a SmallKeySet<T, K, N, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Iter<'a, T, K> {
+        Iter {
+            iter: self.storage.iter_with_keys(),
+        }
+    }
+}
+
+/// PartialEq for SmallKeySet, inline or spilled doesn't matter
+impl<T, K, const N: usize, F> PartialEq for SmallKeySet<T, K, N, F> where K: Eq + Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.storage.len() == other.storage.len()
+        && self.iter_with_keys().all(|(key, _)| other.storage.get(key).is_some())
+    }
+}
+
+/// Debug for SmallKeySet
+impl<T, K, const N: usize, F> fmt::Debug for SmallKeySet<T, K, N, F> where T: fmt::Debug, K: fmt::Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter_with_keys()).finish()
+    }
+}
+
+/// Extend for SmallKeySet, elements refused under `DuplicatePolicy::Reject` are dropped
+impl<T, K, const N: usize, F> Extend<T> for SmallKeySet<T, K, N, F> where K: Eq + Hash, F: GetKey<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.insert_all(iter);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+enum InnerIter<'a, T, K> {
+    Inline(slice::Iter<'a, Option<(K, T)>>),
+    Spilled(hash_map::Iter<'a, K, T>),
+}
+
+pub struct IterWithKeys<'a, T, K> {
+    inner: InnerIter<'a, T, K>,
+}
+
+impl<'a, T, K> Iterator for IterWithKeys<'a, T, K> {
+    type Item = (&'a K, &'a T);
+
+    fn next(&mut self) -> Option<(&'a K, &'a T)> {
+        match &mut self.inner {
+            InnerIter::Inline(iter) => iter.next().map(|slot| {
+                let (k, v) = slot.as_ref().expect("inline slot below len is empty");
+                (k, v)
+            }),
+            InnerIter::Spilled(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            InnerIter::Inline(iter) => iter.size_hint(),
+            InnerIter::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<T, K> ExactSizeIterator for IterWithKeys<'_, T, K> {}
+impl<T, K> FusedIterator for IterWithKeys<'_, T, K> {}
+
+impl<T, K> Clone for IterWithKeys<'_, T, K> {
+    fn clone(&self) -> Self {
+        let inner = match &self.inner {
+            InnerIter::Inline(iter) => InnerIter::Inline(iter.clone()),
+            InnerIter::Spilled(iter) => InnerIter::Spilled(iter.clone()),
+        };
+
+        IterWithKeys { inner }
+    }
+}
+
+pub struct Iter<'a, T, K> {
+    iter: IterWithKeys<'a, T, K>,
+}
+
+impl<'a, T, K> Iterator for Iter<'a, T, K> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K> ExactSizeIterator for Iter<'_, T, K> {}
+impl<T, K> FusedIterator for Iter<'_, T, K> {}
+
+impl<T, K> Clone for Iter<'_, T, K> {
+    fn clone(&self) -> Self {
+        Iter { iter: self.iter.clone() }
+    }
+}
+
+/// The inline slots by value, `len` of them are `Some`.
+struct InlineIntoIter<T, K, const N: usize> {
+    iter: array::IntoIter<Option<(K, T)>, N>,
+    len: usize,
+}
+
+impl<T, K, const N: usize> Iterator for InlineIntoIter<T, K, N> {
+    type Item = (K, T);
+
+    fn next(&mut self) -> Option<(K, T)> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+
+        self.iter.next().flatten()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+enum IntoPairs<T, K, const N: usize> {
+    Inline(InlineIntoIter<T, K, N>),
+    Spilled(hash_map::IntoIter<K, T>),
+}
+
+impl<T, K, const N: usize> Iterator for IntoPairs<T, K, N> {
+    type Item = (K, T);
+
+    fn next(&mut self) -> Option<(K, T)> {
+        match self {
+            IntoPairs::Inline(iter) => iter.next(),
+            IntoPairs::Spilled(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IntoPairs::Inline(iter) => iter.size_hint(),
+            IntoPairs::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+/// Owning iterator of `SmallKeySet`
+pub struct IntoIter<T, K, const N: usize> {
+    iter: IntoPairs<T, K, N>,
+}
+
+impl<T, K, const N: usize> Iterator for IntoIter<T, K, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, K, const N: usize> ExactSizeIterator for IntoIter<T, K, N> {}
+impl<T, K, const N: usize> FusedIterator for IntoIter<T, K, N> {}
+
+enum InnerDrain<'a, T, K, const N: usize> {
+    Inline(InlineIntoIter<T, K, N>),
+    Spilled(hash_map::Drain<'a, K, T>),
+}
+
+/// Draining iterator of `SmallKeySet`
+pub struct Drain<'a, T, K, const N: usize> {
+    inner: InnerDrain<'a, T, K, N>,
+}
+
+impl<T, K, const N: usize> Iterator for Drain<'_, T, K, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match &mut self.inner {
+            InnerDrain::Inline(iter) => iter.next().map(|(_, v)| v),
+            InnerDrain::Spilled(iter) => iter.next().map(|(_, v)| v),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            InnerDrain::Inline(iter) => iter.size_hint(),
+            InnerDrain::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<T, K, const N: usize> ExactSizeIterator for Drain<'_, T, K, N> {}
+impl<T, K, const N: usize> FusedIterator for Drain<'_, T, K, N> {}
+
+enum InnerExtractIf<'a, T, K, const N: usize, P> {
+    Inline {
+        slots: &'a mut [Option<(K, T)>; N],
+        len: &'a mut usize,
+        index: usize,
+        pred: P,
+    },
+    Spilled(key_set::ExtractIf<'a, T, K, P>),
+}
+
+/// Iterator of `SmallKeySet::extract_if`.
+///
+/// Inline, an extracted element is replaced by the last one, still unvisited.
+pub struct ExtractIf<'a, T, K, const N: usize, P> {
+    inner: InnerExtractIf<'a, T, K, N, P>,
+}
+
+impl<T, K, const N: usize, P> Iterator for ExtractIf<'_, T, K, N, P> where K: Eq + Hash, P: FnMut(&T) -> bool {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match &mut self.inner {
+            InnerExtractIf::Inline { slots, len, index, pred } => {
+                while *index < **len {
+                    let (_, v) = slots[*index].as_ref().expect("inline slot below len is empty");
+
+                    if pred(v) {
+                        return Some(Storage::swap_remove(slots, len, *index));
+                    }
+                    *index += 1;
+                }
+
+                None
+            },
+            InnerExtractIf::Spilled(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            InnerExtractIf::Inline { len, index, .. } => (0, Some(**len - *index)),
+            InnerExtractIf::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<T, K, const N: usize, P> FusedIterator for ExtractIf<'_, T, K, N, P> where K: Eq + Hash, P: FnMut(&T) -> bool {}
@@ -0,0 +1,331 @@
+use std::borrow::{ Borrow };
+use std::cmp::{ Reverse };
+use std::collections::{ BinaryHeap, HashMap };
+use std::convert::{ TryFrom };
+use std::hash::{ Hash };
+use std::iter::{ FusedIterator, IntoIterator };
+use std::sync::{ Arc };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::time::{ Duration, Instant };
+use std::mem;
+use std::fmt;
+
+use crate::key_set::{ GetKey, GetKeyType };
+use crate::slab::{ self, Slab };
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Clock
+
+/// The time source of a `TtlKeySet`.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, clones share the same time.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed_nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            start: Instant::now(),
+            elapsed_nanos: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Move the time forward by `by`, saturating at `u64::MAX` nanoseconds
+    /// elapsed, about 584 years.
+    pub fn advance(&self, by: Duration) {
+        let by = u64::try_from(by.as_nanos()).unwrap_or(u64::MAX);
+
+        let _ = self.elapsed_nanos.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |elapsed| {
+            Some(elapsed.saturating_add(by))
+        });
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + Duration::from_nanos(self.elapsed_nanos.load(Ordering::SeqCst))
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// TtlKeySet
+
+/// A key set whose elements expire after a time-to-live.
+///
+/// An expired element is invisible to every read but still takes room until
+/// `purge_expired` reclaims it, or an insert of the same key replaces it.
+/// Purging pops a min-heap of deadlines, so it only visits what it removes,
+/// plus the heap entries of elements replaced or removed in the meantime.
+pub struct TtlKeySet<T, K, F = GetKeyType<T, K>, C = SystemClock> {
+    get_key: F,
+    clock: C,
+    default_ttl: Duration,
+    slots: Slab<Slot<T>>,
+    /// Deadline, slot id and generation of every insert, stale ones are skipped.
+    deadlines: BinaryHeap<Reverse<(Instant, usize, u64)>>,
+    generation: u64,
+    _index_map: HashMap<K, usize>,
+}
+
+struct Slot<T> {
+    value: T,
+    /// `None` for a TTL too long to represent, the element never expires.
+    deadline: Option<Instant>,
+    generation: u64,
+}
+
+impl<T> Slot<T> {
+    fn is_live(&self, now: Instant) -> bool {
+        self.deadline.map_or(true, |deadline| deadline > now)
+    }
+}
+
+impl<T, K> TtlKeySet<T, K> where K: Eq + Hash {
+    pub fn new(get_key: GetKeyType<T, K>, default_ttl: Duration) -> Self {
+        Self::with_get_key(get_key, default_ttl)
+    }
+}
+
+impl<T, K, F> TtlKeySet<T, K, F> where K: Eq + Hash, F: GetKey<T, K> {
+    pub fn with_get_key(get_key: F, default_ttl: Duration) -> Self {
+        Self::with_clock(get_key, default_ttl, SystemClock)
+    }
+}
+
+impl<T, K, F, C> TtlKeySet<T, K, F, C> where K: Eq + Hash, F: GetKey<T, K>, C: Clock {
+    pub fn with_clock(get_key: F, default_ttl: Duration, clock: C) -> Self {
+        TtlKeySet {
+            get_key,
+            clock,
+            default_ttl,
+            slots: Slab::new(),
+            deadlines: BinaryHeap::new(),
+            generation: 0,
+            _index_map: HashMap::new(),
+        }
+    }
+
+    /// Insert `value` for the default TTL.
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        self.insert_with_ttl(value, self.default_ttl)
+    }
+
+    /// Insert `value` for `ttl`, returning the live element it replaced.
+    ///
+    /// A `ttl` that overflows `Instant`, e.g. `Duration::MAX`, never expires.
+    pub fn insert_with_ttl(&mut self, value: T, ttl: Duration) -> Option<T> {
+        let now = self.clock.now();
+        let key = self.get_key.get_key(&value);
+        let deadline = now.checked_add(ttl);
+
+        self.generation += 1;
+        let slot = Slot { value, deadline, generation: self.generation };
+
+        let (id, old) = match self._index_map.get(&key) {
+            Some(&id) => (id, Some(mem::replace(self.slots.get_mut(id), slot))),
+            None => {
+                let id = self.slots.insert(slot);
+                self._index_map.insert(key, id);
+
+                (id, None)
+            },
+        };
+
+        if let Some(deadline) = deadline {
+            self.deadlines.push(Reverse((deadline, id, self.generation)));
+            self.compact_deadlines();
+        }
+
+        old.filter(|old| old.is_live(now)).map(|old| old.value)
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.contains_key(&self.get_key.get_key(value))
+    }
+
+    pub fn get(&self, value: &T) -> Option<&T> {
+        self.get_by_key(&self.get_key.get_key(value))
+    }
+
+    pub fn remove(&mut self, value: &T) -> bool {
+        let key = self.get_key.get_key(value);
+        self.take_by_key(&key).is_some()
+    }
+
+    /// Drop the expired elements, returning how many there were.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let mut purged = 0;
+
+        while let Some(&Reverse((deadline, id, generation))) = self.deadlines.peek() {
+            if deadline > now {
+                break;
+            }
+            self.deadlines.pop();
+
+            let current = self.slots.slots()[id].as_ref().is_some_and(|slot| slot.generation == generation);
+            if current {
+                let slot = self.slots.remove(id);
+                self._index_map.remove(&self.get_key.get_key(&slot.value));
+                purged += 1;
+            }
+        }
+
+        purged
+    }
+
+    /// Rebuild the heap from the live slots once stale entries outnumber them.
+    fn compact_deadlines(&mut self) {
+        if self.deadlines.len() <= 2 * self._index_map.len() + 16 {
+            return;
+        }
+
+        self.deadlines = self.slots
+            .slots()
+            .iter()
+            .enumerate()
+            .filter_map(|(id, slot)| {
+                let slot = slot.as_ref()?;
+                slot.deadline.map(|deadline| Reverse((deadline, id, slot.generation)))
+            })
+            .collect();
+    }
+}
+
+/// Key lookups accept any borrowed form of `K`, e.g. `&str` for a `String` key.
+impl<T, K, F, C> TtlKeySet<T, K, F, C> where K: Eq + Hash, C: Clock {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.get_by_key(key).is_some()
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.live_slot(key, self.clock.now()).map(|slot| &slot.value)
+    }
+
+    /// Time left before the element of `key` expires, `Duration::MAX` if it never does.
+    pub fn remaining_ttl<Q>(&self, key: &Q) -> Option<Duration> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let now = self.clock.now();
+
+        self.live_slot(key, now).map(|slot| slot.deadline.map_or(Duration::MAX, |deadline| deadline - now))
+    }
+
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.take_by_key(key).is_some()
+    }
+
+    /// Remove the element of `key`, returning it if it hadn't expired.
+    pub fn take_by_key<Q>(&mut self, key: &Q) -> Option<T> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let id = self._index_map.remove(key)?;
+        let slot = self.slots.remove(id);
+
+        slot.is_live(self.clock.now()).then_some(slot.value)
+    }
+
+    /// Number of live elements, O(n).
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Number of elements held, the expired ones not yet purged included.
+    pub fn stored_len(&self) -> usize {
+        self._index_map.len()
+    }
+
+    pub fn clear(&mut self) {
+        self._index_map.clear();
+        self.slots.clear();
+        self.deadlines.clear();
+    }
+
+    /// Iterate the live elements, as of the call.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            iter: self.slots.iter(),
+            now: self.clock.now(),
+        }
+    }
+
+    fn live_slot<Q>(&self, key: &Q, now: Instant) -> Option<&Slot<T>> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let &id = self._index_map.get(key)?;
+
+        Some(self.slots.get(id)).filter(|slot| slot.is_live(now))
+    }
+}
+
+impl<'a, T, K, F, C> IntoIterator for &'a TtlKeySet<T, K, F, C> where K: Eq + Hash, C: Clock {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Debug for TtlKeySet, the live elements only
+impl<T, K, F, C> fmt::Debug for TtlKeySet<T, K, F, C> where T: fmt::Debug, K: Eq + Hash, C: Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Iterators
+
+pub struct Iter<'a, T> {
+    iter: slab::Iter<'a, Slot<T>>,
+    now: Instant,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let now = self.now;
+
+        self.iter.by_ref().find(|slot| slot.is_live(now)).map(|slot| &slot.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.iter.len()))
+    }
+}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Iter {
+            iter: self.iter.clone(),
+            now: self.now,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+#![allow(dead_code, clippy::derived_hash_with_manual_eq, clippy::inconsistent_digit_grouping)]
+
+use std::fmt;
+
+use key_set::GetKeyType;
+
+#[derive(Hash, Clone, fmt::Debug)]
+pub struct Person {
+    pub id: u32,
+    pub name: String,
+    pub phone: u64,
+}
+
+impl PartialEq for Person {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+pub fn gen_person_sample(identifier: &str) -> Person {
+
+    match identifier {
+        "a" => Person{
+            id: 5,
+            name: "Janet".to_string(),
+            phone: 555_666_7777,
+        },
+        "b" => Person {
+            id: 6,
+            name: "Byn".to_string(),
+            phone: 222_333_4444,
+        },
+        "c" => Person {
+            id: 7,
+            name: "Janet".to_string(),
+            phone: 888_999_0000,
+        },
+        "d" => Person {
+            id: 8,
+            name: "Jun".to_string(),
+            phone: 888_999_0000,
+        },
+        "e" => Person {
+            id: 9,
+            name: "Kat".to_string(),
+            phone: 678_123_4567,
+        },
+        _ => Person {
+            id: 0,
+            name: "anonymous".to_string(),
+            phone: 000_000_0000,
+        }
+    }
+}
+
+/// A person without a phone, for the sets that only care about the key.
+pub fn person(id: u32, name: &str) -> Person {
+    Person { id, name: name.to_string(), phone: 0 }
+}
+
+pub static GET_KEY_FUNC:GetKeyType<Person, u32> = |person: &Person| person.id;
+
+/// The ids, in iteration order.
+pub fn ids<'a>(iter: impl IntoIterator<Item=&'a Person>) -> Vec<u32> {
+    iter.into_iter().map(|person| person.id).collect()
+}
+
+/// The ids, sorted, for the sets without a stable order.
+pub fn sorted_ids<'a>(iter: impl IntoIterator<Item=&'a Person>) -> Vec<u32> {
+    let mut res = ids(iter);
+    res.sort_unstable();
+    res
+}
@@ -0,0 +1,193 @@
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::thread;
+
+use key_set::{ ConcurrentKeySet, DuplicatePolicy, InsertOutcome, GetKeyType };
+
+#[derive(Clone, Debug, PartialEq)]
+struct Counter {
+    id: u32,
+    hits: u64,
+}
+
+fn counter(id: u32) -> Counter {
+    Counter { id, hits: 0 }
+}
+
+static GET_KEY_FUNC: GetKeyType<Counter, u32> = |counter: &Counter| counter.id;
+
+#[test]
+fn shared_set_io() {
+    let set = ConcurrentKeySet::new(GET_KEY_FUNC);
+    assert!(set.shard_count() > 0);
+
+    assert_eq!(set.insert(counter(1)), InsertOutcome::Inserted);
+    assert!(matches!(set.insert(counter(1)), InsertOutcome::Replaced(_)));
+    set.insert(counter(2));
+
+    assert!(set.contains(&counter(1)));
+    assert!(set.contains_key(&2));
+    assert!(!set.contains_key(&3));
+    assert_eq!(set.len(), 2);
+
+    assert_eq!(set.update_with(&1, |counter| { counter.hits += 5; counter.hits }), Some(5));
+    assert_eq!(set.update_with(&3, |counter| counter.hits), None);
+    assert_eq!(set.get_cloned(&counter(1)).unwrap().hits, 5);
+    assert_eq!(set.get_cloned_by_key(&2), Some(counter(2)));
+
+    assert!(set.remove(&counter(2)));
+    assert!(!set.remove_by_key(&2));
+    assert_eq!(set.take_by_key(&1).unwrap().hits, 5);
+    assert!(set.is_empty());
+}
+
+#[test]
+fn duplicate_policy_and_shards() {
+    let mut set = ConcurrentKeySet::from_intoiter_with(GET_KEY_FUNC, 1, (0..10).map(counter));
+    assert_eq!(set.shard_count(), 1);
+    assert_eq!(set.len(), 10);
+
+    set.set_duplicate_policy(DuplicatePolicy::Merge(|old, new| old.hits += new.hits + 1));
+    assert_eq!(set.insert(counter(3)), InsertOutcome::Merged);
+    assert_eq!(set.get_cloned_by_key(&3).unwrap().hits, 1);
+
+    let mut ids: Vec<u32> = set.into_iter().map(|counter| counter.id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, (0..10).collect::<Vec<u32>>());
+}
+
+#[test]
+#[should_panic]
+fn zero_shards() {
+    ConcurrentKeySet::with_shard_count(GET_KEY_FUNC, 0);
+}
+
+#[test]
+fn update_with_panic_keeps_element() {
+    let set = ConcurrentKeySet::with_shard_count(GET_KEY_FUNC, 4);
+    set.insert(counter(1));
+
+    let res = thread::scope(|s| s.spawn(|| set.update_with(&1, |counter| {
+        counter.hits += 1;
+        panic!("callback failed");
+    })).join());
+    assert!(res.is_err());
+
+    assert_eq!(set.update_with(&1, |counter| counter.hits), Some(1));
+    assert_eq!(set.len(), 1);
+}
+
+#[test]
+fn update_with_key_change_then_panic() {
+    let set = ConcurrentKeySet::with_shard_count(GET_KEY_FUNC, 4);
+    set.insert(counter(1));
+    set.insert(counter(2));
+
+    let res = thread::scope(|s| s.spawn(|| set.update_with(&1, |counter| {
+        counter.id = 7;
+        panic!("callback failed");
+    })).join());
+    assert!(res.is_err());
+
+    // no element is left under a key it doesn't have
+    assert!(!set.contains_key(&1));
+    assert!(set.get_cloned_by_key(&1).is_none());
+    assert!(!set.contains(&counter(7)));
+    assert_eq!(set.len(), 1);
+
+    set.insert(counter(7));
+    assert_eq!(set.len(), 2);
+    assert_eq!(set.update_with(&7, |counter| counter.id), Some(7));
+}
+
+#[test]
+fn update_with_key_change_panics() {
+    let set = ConcurrentKeySet::with_shard_count(GET_KEY_FUNC, 4);
+    set.insert(counter(1));
+    set.insert(counter(2));
+
+    let res = thread::scope(|s| s.spawn(|| set.update_with(&1, |counter| counter.id = 7)).join());
+    assert!(res.is_err());
+
+    // the changed element is dropped, the set is still usable
+    assert!(!set.contains_key(&1));
+    assert!(!set.contains_key(&7));
+    assert!(set.update_with(&2, |counter| counter.hits += 1).is_some());
+    assert_eq!(set.len(), 1);
+}
+
+#[test]
+fn stress_disjoint_inserts() {
+    let set = ConcurrentKeySet::with_shard_count(GET_KEY_FUNC, 8);
+
+    thread::scope(|s| {
+        for t in 0..8 {
+            let set = &set;
+            s.spawn(move || {
+                for i in 0..1000 {
+                    set.insert(counter(t * 1000 + i));
+                }
+                // then remove the odd ones again
+                for i in (1..1000).step_by(2) {
+                    assert!(set.remove_by_key(&(t * 1000 + i)));
+                }
+            });
+        }
+    });
+
+    assert_eq!(set.len(), 8 * 500);
+    assert!((0..8000).all(|id| set.contains_key(&id) == (id % 2 == 0)));
+}
+
+#[test]
+fn stress_update_with() {
+    let set = ConcurrentKeySet::with_shard_count(GET_KEY_FUNC, 4);
+    for id in 0..16 {
+        set.insert(counter(id));
+    }
+
+    thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                for i in 0..4000 {
+                    set.update_with(&(i % 16), |counter| counter.hits += 1).unwrap();
+                }
+            });
+        }
+    });
+
+    // no lost update
+    for id in 0..16 {
+        assert_eq!(set.get_cloned_by_key(&id).unwrap().hits, 8 * 4000 / 16);
+    }
+}
+
+#[test]
+fn stress_snapshot_is_consistent() {
+    let set = ConcurrentKeySet::with_shard_count(GET_KEY_FUNC, 16);
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        // keys are inserted in order, so every point in time holds a prefix of them
+        s.spawn(|| {
+            for id in 0..5000 {
+                set.insert(counter(id));
+            }
+            done.store(true, Ordering::Release);
+        });
+
+        for _ in 0..4 {
+            s.spawn(|| {
+                while !done.load(Ordering::Acquire) {
+                    let snapshot = set.snapshot();
+                    let n = snapshot.len() as u32;
+
+                    let mut ids: Vec<u32> = snapshot.map(|counter| counter.id).collect();
+                    ids.sort_unstable();
+                    assert_eq!(ids, (0..n).collect::<Vec<u32>>());
+                }
+            });
+        }
+    });
+
+    assert_eq!(set.snapshot().len(), 5000);
+}
@@ -0,0 +1,126 @@
+use std::error::Error;
+
+use key_set::{ KeyBiSet, GetKeyType };
+
+#[derive(Clone, Debug, PartialEq)]
+struct Account {
+    id: u32,
+    external_id: String,
+    balance: i64,
+}
+
+fn account(id: u32, external_id: &str) -> Account {
+    Account { id, external_id: external_id.to_string(), balance: 0 }
+}
+
+static GET_LEFT_KEY: GetKeyType<Account, u32> = |account: &Account| account.id;
+static GET_RIGHT_KEY: GetKeyType<Account, String> = |account: &Account| account.external_id.clone();
+
+fn gen_account_set() -> KeyBiSet<Account, u32, String> {
+    let mut set = KeyBiSet::new(GET_LEFT_KEY, GET_RIGHT_KEY);
+    set.insert(account(1, "ext-a")).unwrap();
+    set.insert(account(2, "ext-b")).unwrap();
+    set.insert(account(3, "ext-c")).unwrap();
+
+    set
+}
+
+#[test]
+fn lookup_both_sides() {
+    let set = gen_account_set();
+
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.get_by_left(&2).unwrap().external_id, "ext-b");
+    // borrowed form of the key
+    assert_eq!(set.get_by_right("ext-c").unwrap().id, 3);
+    assert!(set.get_by_left(&4).is_none());
+    assert!(set.get_by_right("ext-d").is_none());
+
+    assert!(set.contains_left(&1));
+    assert!(set.contains_right("ext-a"));
+    assert!(set.collides(&account(9, "ext-a")));
+    assert!(!set.collides(&account(9, "ext-z")));
+
+    let mut ids: Vec<u32> = set.iter().map(|account| account.id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![1, 2, 3]);
+}
+
+#[test]
+fn insert_collision() {
+    let mut set = gen_account_set();
+
+    // clashes on both sides with two different elements
+    let err = set.insert(account(1, "ext-b")).unwrap_err();
+    assert_eq!(set.get_by_left(err.left_key().unwrap()).unwrap().external_id, "ext-a");
+    assert_eq!(set.get_by_right(err.right_key().unwrap()).unwrap().id, 2);
+    assert_eq!(err.to_string(), "both keys of the element are already held");
+    assert_eq!(err.into_inner(), account(1, "ext-b"));
+
+    let err = set.insert(account(9, "ext-c")).unwrap_err();
+    assert!(err.left_key().is_none());
+    assert_eq!(err.right_key().unwrap(), "ext-c");
+
+    let err = set.insert(account(3, "ext-z")).unwrap_err();
+    assert_eq!(err.to_string(), "the left key of the element is already held");
+
+    // nothing changed
+    assert_eq!(set.len(), 3);
+    assert!(set.get_by_left(&9).is_none());
+    assert!(set.get_by_right("ext-z").is_none());
+    assert_eq!(set.get_by_right("ext-b").unwrap().id, 2);
+}
+
+#[test]
+fn collision_is_an_owned_error() {
+    fn insert_all(set: &mut KeyBiSet<Account, u32, String>, ids: &[u32]) -> Result<(), Box<dyn Error>> {
+        for &id in ids {
+            set.insert(account(id, &format!("ext-{}", id)))?;
+        }
+        Ok(())
+    }
+
+    let mut set = gen_account_set();
+
+    let err = insert_all(&mut set, &[4, 2, 5]).unwrap_err();
+    assert_eq!(err.to_string(), "the left key of the element is already held");
+    // the set is usable again right away
+    assert_eq!(set.len(), 4);
+}
+
+#[test]
+fn remove_both_sides() {
+    let mut set = gen_account_set();
+
+    let taken = set.take_by_left(&1).unwrap();
+    assert_eq!(taken.external_id, "ext-a");
+    assert!(!set.contains_right("ext-a"));
+
+    assert!(set.remove_by_right("ext-b"));
+    assert!(!set.contains_left(&2));
+    assert!(!set.remove_by_right("ext-b"));
+    assert!(!set.remove_by_left(&2));
+    assert_eq!(set.len(), 1);
+
+    // both freed keys can be taken again
+    set.insert(account(2, "ext-a")).unwrap();
+    assert_eq!(set.get_by_right("ext-a").unwrap().id, 2);
+    assert_eq!(set.iter().len(), 2);
+
+    assert!(set.take_by_right("ext-c").is_some());
+    assert!(set.take_by_left(&2).is_some());
+    assert!(set.is_empty());
+}
+
+#[test]
+fn closure_get_keys() {
+    let prefix = "ext-";
+    let mut set = KeyBiSet::with_get_keys(
+        |account: &Account| account.id,
+        move |account: &Account| account.external_id.trim_start_matches(prefix).to_string()
+    );
+
+    set.insert(account(1, "ext-a")).unwrap();
+    assert!(set.insert(account(2, "a")).is_err());
+    assert_eq!(set.get_by_right("a").unwrap().balance, 0);
+}
@@ -0,0 +1,202 @@
+#![allow(clippy::inconsistent_digit_grouping)]
+
+mod common;
+
+use key_set::{ KeyBTreeSet, KeySet, DuplicatePolicy, InsertOutcome, Bias, debug_key };
+
+use common::{ Person, GET_KEY_FUNC, gen_person_sample, ids };
+
+fn gen_person_set(identifiers: &[&str]) -> KeyBTreeSet<Person, u32> {
+    KeyBTreeSet::from_intoiter(GET_KEY_FUNC, identifiers.iter().map(|x| gen_person_sample(x)))
+}
+
+#[test]
+fn create_keybtreeset_basictype() {
+    let mut myset = KeyBTreeSet::new(debug_key);
+
+    myset.insert("c");
+    myset.insert("a");
+    myset.insert("b");
+
+    assert!(myset.contains(&"a"));
+    assert!(!myset.contains(&"d"));
+    assert_eq!(myset.iter().cloned().collect::<Vec<&str>>(), vec!["a", "b", "c"]);
+
+    // test remove
+    myset.remove(&"a");
+    myset.remove(&"c");
+    assert!(!myset.contains(&"a"));
+    assert!(myset.contains(&"b"));
+    assert!(!myset.contains(&"c"));
+}
+
+#[test]
+fn tellme_set_relationship_struct() {
+    let set1 = gen_person_set(&["a", "b", "c"]);
+    let set2 = gen_person_set(&["b", "a"]);
+
+    assert!(set1.is_superset(&set2));
+    assert!(set2.is_subset(&set1));
+
+    let set4 = gen_person_set(&[]);
+    assert!(set4.is_empty());
+
+    assert!(set1.is_disjoint(&set4));
+    assert!(set4.is_disjoint(&set1));
+}
+
+#[test]
+fn set_op_struct() {
+    let set1 = gen_person_set(&["c", "a", "b"]);
+    let set2 = gen_person_set(&["e", "b", "d"]);
+
+    assert_eq!(ids(set1.intersection(&set2).iter()), vec![6]);
+    assert_eq!(ids(set1.union(&set2).iter()), vec![5, 6, 7, 8, 9]);
+    assert_eq!(ids(set1.difference(&set2).iter()), vec![5, 7]);
+    assert_eq!(ids(set1.symmetric_difference(&set2).iter()), vec![5, 7, 8, 9]);
+
+    let mut byn = gen_person_sample("b");
+    byn.phone = 0;
+    let set3 = KeyBTreeSet::from_intoiter(GET_KEY_FUNC, vec![byn]);
+
+    assert_eq!(set1.union(&set3).get_by_key(&6).unwrap().phone, 222_333_4444);
+    assert_eq!(set1.union_by(&set3, Bias::Right).get_by_key(&6).unwrap().phone, 0);
+
+    let merged = set1.intersection_by(&set3, Bias::Merge(|l, r| Person { phone: l.phone + r.phone, ..l.clone() }));
+    assert_eq!(merged.first().unwrap().phone, 222_333_4444);
+}
+
+#[test]
+fn set_io_struct() {
+    let mut set1 = gen_person_set(&["a", "b", "c"]);
+
+    assert!(set1.remove(&gen_person_sample("a")));
+    assert!(!set1.contains(&gen_person_sample("a")));
+    assert!(!set1.remove(&gen_person_sample("e")));
+
+    match set1.take(&gen_person_sample("b")) {
+        Some(v) => assert_eq!(v, gen_person_sample("b")),
+        None => unreachable!()
+    }
+
+    assert!(!set1.contains(&gen_person_sample("b")));
+    assert_eq!(set1.get(&gen_person_sample("c")), Some(&gen_person_sample("c")));
+}
+
+#[test]
+fn set_io_by_key() {
+    let get_key = |person: &Person| String::from(&person.name);
+    let mut set1 = KeyBTreeSet::with_get_key(get_key);
+    set1.extend(vec![gen_person_sample("a"), gen_person_sample("b"), gen_person_sample("d")]);
+
+    // borrowed form of the key
+    assert!(set1.contains_key("Janet"));
+    assert!(!set1.contains_key("Kat"));
+    assert_eq!(set1.get_by_key("Byn"), Some(&gen_person_sample("b")));
+
+    assert!(set1.remove_by_key("Janet"));
+    assert!(!set1.remove_by_key("Janet"));
+    assert_eq!(set1.take_by_key("Jun"), Some(gen_person_sample("d")));
+    assert_eq!(set1.len(), 1);
+}
+
+#[test]
+fn insert_outcome() {
+    let mut set1 = gen_person_set(&["a", "b"]);
+
+    let mut janet = gen_person_sample("a");
+    janet.phone = 0;
+
+    match set1.insert(janet) {
+        InsertOutcome::Replaced(old) => assert_eq!(old.phone, 555_666_7777),
+        _ => unreachable!()
+    }
+    assert_eq!(set1.first().unwrap().phone, 0);
+
+    assert!(set1.try_insert(gen_person_sample("a")).is_err());
+    assert_eq!(set1.try_insert(gen_person_sample("c")).unwrap().id, 7);
+
+    set1.set_duplicate_policy(DuplicatePolicy::Merge(|old, new| old.phone += new.phone));
+    assert_eq!(set1.insert(gen_person_sample("c")), InsertOutcome::Merged);
+    assert_eq!(set1.last().unwrap().phone, 2 * 888_999_0000);
+}
+
+#[test]
+fn ordered_access() {
+    let mut set1 = gen_person_set(&["c", "a", "e", "b", "d"]);
+
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7, 8, 9]);
+    assert_eq!(ids(set1.iter().rev()), vec![9, 8, 7, 6, 5]);
+    assert_eq!(set1.keys().cloned().collect::<Vec<u32>>(), vec![5, 6, 7, 8, 9]);
+
+    // test range
+    assert_eq!(ids(set1.range(6..8)), vec![6, 7]);
+    assert_eq!(ids(set1.range(7..)), vec![7, 8, 9]);
+    assert_eq!(ids(set1.range(..=6).rev()), vec![6, 5]);
+    assert_eq!(set1.range(10..).count(), 0);
+
+    // test first / last
+    assert_eq!(set1.first().unwrap().id, 5);
+    assert_eq!(set1.last().unwrap().id, 9);
+
+    assert_eq!(set1.pop_first().unwrap().id, 5);
+    assert_eq!(set1.pop_last().unwrap().id, 9);
+    assert_eq!(ids(set1.iter()), vec![6, 7, 8]);
+
+    // test split_off
+    let set2 = set1.split_off(&7);
+    assert_eq!(ids(set1.iter()), vec![6]);
+    assert_eq!(ids(set2.iter()), vec![7, 8]);
+    assert!(set2.contains(&gen_person_sample("c")));
+
+    let mut set3 = gen_person_set(&[]);
+    assert!(set3.pop_first().is_none());
+    assert!(set3.last().is_none());
+}
+
+#[test]
+fn retain_drain_extract_if() {
+    let mut set1 = gen_person_set(&["a", "b", "c", "d", "e"]);
+
+    set1.retain(|person| person.id != 9);
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7, 8]);
+
+    let extracted: Vec<u32> = set1.extract_if(|person| person.name == "Janet")
+        .map(|person| person.id)
+        .collect();
+    assert_eq!(extracted, vec![5, 7]);
+    assert_eq!(ids(set1.iter()), vec![6, 8]);
+
+    // dropped early, the unvisited elements stay
+    assert_eq!(set1.extract_if(|_| true).next().map(|person| person.id), Some(6));
+    assert_eq!(ids(set1.iter()), vec![8]);
+
+    let drain = set1.drain();
+    assert_eq!(drain.len(), 1);
+    assert_eq!(drain.map(|person| person.id).collect::<Vec<u32>>(), vec![8]);
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn set_op_in_place() {
+    let mut set1 = gen_person_set(&["c", "a", "b"]);
+
+    let mut byn = gen_person_sample("b");
+    byn.phone = 0;
+    let mut set2 = gen_person_set(&["e"]);
+    set2.insert(byn);
+
+    // self keeps its own element on a shared key
+    set1.union_with(set2);
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7, 9]);
+    assert_eq!(set1.get_by_key(&6).unwrap().phone, 222_333_4444);
+
+    set1.retain_intersection(&gen_person_set(&["a", "b", "c"]));
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7]);
+
+    set1.subtract(&gen_person_set(&["a"]));
+    assert_eq!(ids(set1.iter()), vec![6, 7]);
+
+    set1.symmetric_difference_with(gen_person_set(&["d", "c"]));
+    assert_eq!(ids(set1.iter()), vec![6, 8]);
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use key_set::{ KeyBloomFilter, KeyCuckooFilter, KeyHashSet, debug_key };
+
+use common::{ GET_KEY_FUNC, person };
+
+fn false_positives(might_contain: impl Fn(&u32) -> bool) -> usize {
+    (100_000..110_000).filter(|id| might_contain(id)).count()
+}
+
+#[test]
+fn bloom_no_false_negatives() {
+    let mut filter = KeyBloomFilter::new(GET_KEY_FUNC, 1000, 0.01);
+    assert!(filter.is_empty());
+
+    // an insert reports a key it may have seen, false positives included
+    let fresh = (0..1000).filter(|&id| filter.insert(&person(id, "p"))).count();
+    assert!(fresh > 950);
+    assert!(!filter.insert(&person(7, "p")));
+
+    assert!((0..1000).all(|id| filter.might_contain(&person(id, "p"))));
+    assert!((0..1000).all(|id| filter.might_contain_key(&id)));
+
+    // about 1% of 10000 absent keys, with a generous margin
+    assert!(false_positives(|id| filter.might_contain_key(id)) < 300);
+    assert!(filter.estimated_fp_rate() < 0.03);
+
+    filter.clear();
+    assert!(!filter.might_contain_key(&7));
+}
+
+#[test]
+fn bloom_union() {
+    let mut a = KeyBloomFilter::new(debug_key, 100, 0.01);
+    let mut b = KeyBloomFilter::new(debug_key, 100, 0.01);
+
+    for x in 0..50 {
+        a.insert(&x);
+    }
+    for x in 50..100 {
+        b.insert(&x);
+    }
+
+    let ab = a.union(&b);
+    assert!((0..100).all(|x| ab.might_contain(&x)));
+    assert!(ab.might_contain_key("42"));
+
+    a.union_with(&b);
+    assert!((0..100).all(|x| a.might_contain(&x)));
+    assert_eq!(a.bit_len(), b.bit_len());
+    assert_eq!(a.hash_count(), b.hash_count());
+}
+
+#[test]
+#[should_panic]
+fn bloom_union_of_unlike_filters() {
+    let mut a = KeyBloomFilter::new(debug_key, 100, 0.01);
+    let b = KeyBloomFilter::new(debug_key, 10_000, 0.01);
+
+    a.union_with(&b);
+    a.insert(&1);
+}
+
+#[test]
+#[should_panic]
+fn bloom_bad_fp_rate() {
+    KeyBloomFilter::new(GET_KEY_FUNC, 100, 1.0);
+}
+
+#[test]
+fn from_key_hash_set() {
+    let set = KeyHashSet::from_intoiter(GET_KEY_FUNC, (0..500).map(|id| person(id, "p")));
+
+    let bloom = KeyBloomFilter::from_key_hash_set(&set, 0.01);
+    let cuckoo = KeyCuckooFilter::from_key_hash_set(&set, 0.01);
+
+    for value in set.iter() {
+        assert!(bloom.might_contain(value));
+        assert!(cuckoo.might_contain(value));
+    }
+    assert_eq!(cuckoo.len(), 500);
+    assert!(false_positives(|id| bloom.might_contain_key(id)) < 300);
+    assert!(false_positives(|id| cuckoo.might_contain_key(id)) < 300);
+}
+
+#[test]
+fn cuckoo_insert_and_remove() {
+    let mut filter = KeyCuckooFilter::new(GET_KEY_FUNC, 1000, 0.01);
+
+    for id in 0..1000 {
+        assert!(filter.insert(&person(id, "p")));
+    }
+    assert_eq!(filter.len(), 1000);
+    assert!((0..1000).all(|id| filter.might_contain(&person(id, "p"))));
+    assert!(false_positives(|id| filter.might_contain_key(id)) < 300);
+
+    for id in 0..500 {
+        assert!(filter.remove(&person(id, "p")));
+    }
+    assert_eq!(filter.len(), 500);
+    assert!((500..1000).all(|id| filter.might_contain_key(&id)));
+    assert!((0..500).filter(|id| filter.might_contain_key(id)).count() < 50);
+
+    // a key inserted twice is removed twice
+    filter.insert(&person(700, "p"));
+    assert!(filter.remove_key(&700));
+    assert!(filter.remove_key(&700));
+    assert!(!filter.might_contain_key(&700));
+
+    filter.clear();
+    assert!(filter.is_empty());
+}
+
+#[test]
+fn cuckoo_full() {
+    let mut filter = KeyCuckooFilter::new(debug_key, 8, 0.01);
+    let capacity = filter.capacity() as i32;
+
+    let stored: Vec<i32> = (0..capacity * 2).take_while(|x| filter.insert(x)).collect();
+    assert!(stored.len() <= capacity as usize + 1);
+    assert_eq!(filter.len(), stored.len());
+
+    // full, nothing is lost
+    assert!(!filter.insert(&-1));
+    assert!(stored.iter().all(|x| filter.might_contain(x)));
+
+    // a removal makes room again
+    assert!(filter.remove(&stored[0]));
+    assert!(stored[1..].iter().all(|x| filter.might_contain(x)));
+    assert!(filter.insert(&-1));
+}
+
+#[test]
+fn cuckoo_union() {
+    let mut a = KeyCuckooFilter::new(debug_key, 200, 0.001);
+    let mut b = KeyCuckooFilter::new(debug_key, 200, 0.001);
+
+    for x in 0..80 {
+        a.insert(&x);
+    }
+    for x in 80..160 {
+        b.insert(&x);
+    }
+
+    assert!(a.union_with(&b));
+    assert_eq!(a.len(), 160);
+    assert!((0..160).all(|x| a.might_contain(&x)));
+
+    // the merged keys can be removed like the own ones
+    assert!(a.remove(&100));
+    assert!(a.remove(&10));
+    assert_eq!(a.len(), 158);
+}
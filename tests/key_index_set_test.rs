@@ -0,0 +1,252 @@
+#![allow(clippy::inconsistent_digit_grouping)]
+
+mod common;
+
+use key_set::{ KeyIndexSet, KeySet, DuplicatePolicy, InsertOutcome, debug_key };
+
+use common::{ Person, GET_KEY_FUNC, gen_person_sample, ids };
+
+fn gen_person_set(identifiers: &[&str]) -> KeyIndexSet<Person, u32> {
+    KeyIndexSet::from_intoiter(GET_KEY_FUNC, identifiers.iter().map(|x| gen_person_sample(x)))
+}
+
+#[test]
+fn create_keyindexset_basictype() {
+    let mut myset = KeyIndexSet::new(debug_key);
+
+    myset.insert("a");
+    myset.insert("b");
+    myset.insert("c");
+
+    assert!(myset.contains(&"a"));
+    assert!(myset.contains(&"b"));
+    assert!(myset.contains(&"c"));
+    assert!(!myset.contains(&"d"));
+
+    // test remove
+    myset.remove(&"a");
+    myset.remove(&"c");
+    assert!(!myset.contains(&"a"));
+    assert!(myset.contains(&"b"));
+    assert!(!myset.contains(&"c"));
+}
+
+#[test]
+fn tellme_set_relationship_struct() {
+    let set1 = gen_person_set(&["a", "b", "c"]);
+    let set2 = gen_person_set(&["b", "a"]);
+
+    assert!(set1.is_superset(&set2));
+    assert!(set2.is_subset(&set1));
+
+    // order doesn't matter for equality
+    assert_eq!(set2, gen_person_set(&["a", "b"]));
+
+    let set4 = gen_person_set(&[]);
+    assert!(set4.is_empty());
+
+    assert!(set1.is_disjoint(&set4));
+    assert!(set4.is_disjoint(&set1));
+}
+
+#[test]
+fn set_op_struct() {
+    let set1 = gen_person_set(&["c", "a", "b"]);
+    let set2 = gen_person_set(&["e", "b", "d"]);
+
+    // self's order then other's
+    assert_eq!(ids(&set1.intersection(&set2)), vec![6]);
+    assert_eq!(ids(&set1.union(&set2)), vec![7, 5, 6, 9, 8]);
+    assert_eq!(ids(&set1.difference(&set2)), vec![7, 5]);
+    assert_eq!(ids(&set1.symmetric_difference(&set2)), vec![7, 5, 9, 8]);
+}
+
+#[test]
+fn set_io_struct() {
+    let mut set1 = gen_person_set(&["a", "b", "c"]);
+
+    // test remove
+    assert!(set1.remove(&gen_person_sample("a")));
+    assert!(!set1.contains(&gen_person_sample("a")));
+    assert!(!set1.remove(&gen_person_sample("e")));
+
+    // test take
+    match set1.take(&gen_person_sample("b")) {
+        Some(v) => assert_eq!(v, gen_person_sample("b")),
+        None => unreachable!()
+    }
+
+    assert!(!set1.contains(&gen_person_sample("b")));
+
+    // test get
+    match set1.get(&gen_person_sample("c")) {
+        Some(v) => assert_eq!(v, &gen_person_sample("c")),
+        None => unreachable!()
+    }
+
+    assert!(set1.contains(&gen_person_sample("c")))
+}
+
+#[test]
+fn set_io_by_key() {
+    let get_key = |person: &Person| String::from(&person.name);
+    let mut set1 = KeyIndexSet::with_get_key(get_key);
+    set1.extend(vec![gen_person_sample("a"), gen_person_sample("b"), gen_person_sample("d")]);
+
+    // borrowed form of the key
+    assert!(set1.contains_key("Janet"));
+    assert!(!set1.contains_key("Kat"));
+    assert_eq!(set1.get_by_key("Byn"), Some(&gen_person_sample("b")));
+
+    assert!(set1.remove_by_key("Janet"));
+    assert!(!set1.remove_by_key("Janet"));
+    assert_eq!(set1.take_by_key("Jun"), Some(gen_person_sample("d")));
+    assert_eq!(set1.len(), 1);
+}
+
+#[test]
+fn insert_outcome() {
+    let mut set1 = gen_person_set(&["a", "b"]);
+
+    let mut janet = gen_person_sample("a");
+    janet.phone = 0;
+
+    // replacing keeps the position
+    match set1.insert(janet) {
+        InsertOutcome::Replaced(old) => assert_eq!(old.phone, 555_666_7777),
+        _ => unreachable!()
+    }
+    assert_eq!(set1.first().unwrap().phone, 0);
+
+    assert!(set1.try_insert(gen_person_sample("a")).is_err());
+    assert_eq!(set1.try_insert(gen_person_sample("c")).unwrap().id, 7);
+
+    set1.set_duplicate_policy(DuplicatePolicy::Merge(|old, new| old.phone += new.phone));
+    assert_eq!(set1.insert(gen_person_sample("c")), InsertOutcome::Merged);
+    assert_eq!(set1.get_by_key(&7).unwrap().phone, 2 * 888_999_0000);
+    assert_eq!(ids(&set1), vec![5, 6, 7]);
+}
+
+#[test]
+fn index_access() {
+    let mut set1 = gen_person_set(&["c", "a", "e", "b", "d"]);
+
+    assert_eq!(set1.get_index(1).unwrap().id, 5);
+    assert!(set1.get_index(5).is_none());
+    assert_eq!(set1[2].id, 9);
+    assert_eq!(set1.get_index_of(&6), Some(3));
+    assert_eq!(set1.get_index_of(&1), None);
+    assert_eq!(set1.first().unwrap().id, 7);
+    assert_eq!(set1.last().unwrap().id, 8);
+
+    // trait removal keeps the order
+    assert!(set1.remove_by_key(&5));
+    assert_eq!(ids(&set1), vec![7, 9, 6, 8]);
+
+    assert_eq!(set1.swap_remove_index(0).unwrap().id, 7);
+    assert_eq!(ids(&set1), vec![8, 9, 6]);
+
+    assert_eq!(set1.shift_remove_index(0).unwrap().id, 8);
+    assert_eq!(ids(&set1), vec![9, 6]);
+
+    assert_eq!(set1.shift_remove(&9).unwrap().id, 9);
+    assert!(set1.swap_remove(&9).is_none());
+    assert_eq!(ids(&set1), vec![6]);
+}
+
+#[test]
+fn sort_elements() {
+    let mut set1 = gen_person_set(&["c", "a", "e", "b", "d"]);
+
+    set1.sort_by(|a, b| b.id.cmp(&a.id));
+    assert_eq!(ids(&set1), vec![9, 8, 7, 6, 5]);
+
+    // stable, ties keep the current order
+    set1.sort_by_key(|person| person.name.clone());
+    assert_eq!(ids(&set1), vec![6, 7, 5, 8, 9]);
+
+    // lookups still work after sorting
+    assert_eq!(set1.get_index_of(&5), Some(2));
+    assert_eq!(set1.get_by_key(&8).unwrap().name, "Jun");
+}
+
+#[test]
+fn iter_in_order() {
+    let mut set1 = gen_person_set(&["d", "a", "c"]);
+    set1.insert(gen_person_sample("b"));
+
+    let iter = set1.iter();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.rev().map(|person| person.id).collect::<Vec<u32>>(), vec![6, 7, 5, 8]);
+
+    assert_eq!(set1.keys().cloned().collect::<Vec<u32>>(), vec![8, 5, 7, 6]);
+    for (key, person) in set1.iter_with_keys() {
+        assert_eq!(*key, person.id);
+    }
+
+    let borrowed: Vec<u32> = (&set1).into_iter().map(|person| person.id).collect();
+    let owned: Vec<u32> = set1.into_iter().map(|person| person.id).collect();
+    assert_eq!(borrowed, owned);
+}
+
+#[test]
+fn retain_drain_extract_if() {
+    let mut set1 = gen_person_set(&["a", "b", "c", "d", "e"]);
+
+    set1.retain(|person| person.id != 9);
+    assert_eq!(ids(&set1), vec![5, 6, 7, 8]);
+
+    let extracted: Vec<u32> = set1.extract_if(|person| person.name == "Janet")
+        .map(|person| person.id)
+        .collect();
+    assert_eq!(extracted, vec![5, 7]);
+    assert_eq!(ids(&set1), vec![6, 8]);
+
+    let drain = set1.drain();
+    assert_eq!(drain.len(), 2);
+    assert_eq!(drain.map(|person| person.id).collect::<Vec<u32>>(), vec![6, 8]);
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn extract_if_keeps_order() {
+    let mut set1 = KeyIndexSet::from_intoiter(debug_key, (0..10).rev());
+
+    // dropped early, the unvisited elements stay in place
+    assert_eq!(set1.extract_if(|x| x % 3 == 0).next(), Some(9));
+    assert_eq!(set1.iter().cloned().collect::<Vec<i32>>(), vec![8, 7, 6, 5, 4, 3, 2, 1, 0]);
+
+    // so do they when the iterator is leaked
+    let mut extract = Box::new(set1.extract_if(|x| *x == 8 || *x == 2));
+    assert_eq!(extract.next(), Some(8));
+    std::mem::forget(extract);
+    assert_eq!(set1.iter().cloned().collect::<Vec<i32>>(), vec![7, 6, 5, 4, 3, 2, 1, 0]);
+
+    // a panicking predicate loses nothing
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        set1.extract_if(|x| if *x == 4 { panic!("predicate failed") } else { x % 2 == 0 }).count()
+    }));
+    assert!(result.is_err());
+    assert_eq!(set1.iter().cloned().collect::<Vec<i32>>(), vec![7, 5, 4, 3, 2, 1, 0]);
+
+    let odd: Vec<i32> = set1.extract_if(|x| x % 2 == 1).collect();
+    assert_eq!(odd, vec![7, 5, 3, 1]);
+    assert_eq!(set1.iter().cloned().collect::<Vec<i32>>(), vec![4, 2, 0]);
+}
+
+#[test]
+fn set_op_in_place() {
+    let mut set1 = gen_person_set(&["c", "a", "b"]);
+
+    set1.union_with(gen_person_set(&["e", "b"]));
+    assert_eq!(ids(&set1), vec![7, 5, 6, 9]);
+
+    set1.retain_intersection(&gen_person_set(&["a", "b", "c"]));
+    assert_eq!(ids(&set1), vec![7, 5, 6]);
+
+    set1.subtract(&gen_person_set(&["a"]));
+    assert_eq!(ids(&set1), vec![7, 6]);
+
+    set1.symmetric_difference_with(gen_person_set(&["d", "c"]));
+    assert_eq!(ids(&set1), vec![6, 8]);
+}
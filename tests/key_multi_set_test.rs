@@ -0,0 +1,104 @@
+use key_set::{ KeyMultiSet, GetKeyType };
+
+#[derive(Clone, Debug, PartialEq)]
+struct Event {
+    user_id: u32,
+    kind: &'static str,
+}
+
+fn event(user_id: u32, kind: &'static str) -> Event {
+    Event { user_id, kind }
+}
+
+static GET_KEY_FUNC: GetKeyType<Event, u32> = |event: &Event| event.user_id;
+
+fn gen_event_set(events: &[(u32, &'static str)]) -> KeyMultiSet<Event, u32> {
+    KeyMultiSet::from_intoiter(GET_KEY_FUNC, events.iter().map(|&(user_id, kind)| event(user_id, kind)))
+}
+
+fn kinds(events: &[Event]) -> Vec<&'static str> {
+    events.iter().map(|event| event.kind).collect()
+}
+
+#[test]
+fn keep_every_element() {
+    let mut set1 = gen_event_set(&[(1, "login"), (2, "login"), (1, "click")]);
+    set1.insert(event(1, "logout"));
+
+    assert_eq!(set1.len(), 4);
+    assert_eq!(set1.key_count(), 2);
+    assert_eq!(kinds(set1.get_all(&1)), vec!["login", "click", "logout"]);
+    assert_eq!(set1.count(&1), 3);
+    assert_eq!(set1.count(&3), 0);
+    assert!(set1.get_all(&3).is_empty());
+
+    assert!(set1.contains(&event(2, "anything")));
+    assert!(set1.contains_key(&2));
+    assert!(!set1.contains_key(&3));
+
+    assert_eq!(set1.iter().len(), 4);
+    assert_eq!(set1.iter().filter(|event| event.user_id == 1).count(), 3);
+    assert_eq!((&set1).into_iter().count(), 4);
+    assert_eq!(set1.into_iter().count(), 4);
+}
+
+#[test]
+fn remove_one_and_all() {
+    let mut set1 = gen_event_set(&[(1, "login"), (2, "login"), (1, "click")]);
+
+    // the last inserted goes first
+    assert_eq!(set1.remove_one(&1), Some(event(1, "click")));
+    assert_eq!(set1.remove_one(&1), Some(event(1, "login")));
+    assert_eq!(set1.remove_one(&1), None);
+    assert!(!set1.contains_key(&1));
+    assert_eq!(set1.len(), 1);
+
+    set1.extend(vec![event(2, "click"), event(3, "login")]);
+    assert_eq!(kinds(&set1.remove_all(&2)), vec!["login", "click"]);
+    assert!(set1.remove_all(&2).is_empty());
+    assert_eq!(set1.len(), 1);
+
+    set1.clear();
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn iter_groups() {
+    let set1 = gen_event_set(&[(1, "login"), (2, "login"), (1, "click")]);
+
+    let mut groups: Vec<(u32, Vec<&str>)> = set1.iter_groups()
+        .map(|(key, group)| (*key, kinds(group)))
+        .collect();
+    groups.sort_unstable();
+
+    assert_eq!(groups, vec![(1, vec!["login", "click"]), (2, vec!["login"])]);
+    assert_eq!(set1.iter_groups().len(), set1.key_count());
+}
+
+#[test]
+fn multiset_op() {
+    let set1 = gen_event_set(&[(1, "a"), (1, "b"), (1, "c"), (2, "a")]);
+    let set2 = gen_event_set(&[(1, "x"), (3, "x"), (3, "y")]);
+
+    // union sums the counts, ours first
+    let unioned_set = set1.union(&set2);
+    assert_eq!(unioned_set.len(), 7);
+    assert_eq!(kinds(unioned_set.get_all(&1)), vec!["a", "b", "c", "x"]);
+    assert_eq!(unioned_set.count(&3), 2);
+
+    // intersection keeps the smaller count
+    let intersectioned_set = set1.intersection(&set2);
+    assert_eq!(intersectioned_set, gen_event_set(&[(1, "a")]));
+    assert_eq!(kinds(intersectioned_set.get_all(&1)), vec!["a"]);
+
+    // difference subtracts the counts
+    let differenced_set = set1.difference(&set2);
+    assert_eq!(kinds(differenced_set.get_all(&1)), vec!["a", "b"]);
+    assert_eq!(differenced_set.count(&2), 1);
+    assert!(!differenced_set.contains_key(&3));
+    assert_eq!(differenced_set.len(), 3);
+
+    assert!(set2.difference(&set1.union(&set2)).is_empty());
+    assert_ne!(set1, set2);
+    assert_eq!(set1.union(&set2), set2.union(&set1));
+}
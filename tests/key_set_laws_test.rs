@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+
+use proptest::prelude::*;
+
+use key_set::{ KeyHashSet, KeyIndexSet, KeyBTreeSet, SmallKeySet, KeyVecSet, KeySet, Bias, DuplicatePolicy };
+
+type Elem = (u8, u8);
+
+fn get_key(elem: &Elem) -> u8 {
+    elem.0
+}
+
+fn elems() -> impl Strategy<Value = Vec<Elem>> {
+    prop::collection::vec((0u8..32, any::<u8>()), 0..24)
+}
+
+/// The same laws for every `KeySet` backend.
+macro_rules! set_laws {
+    ($name:ident, $set:ty) => {
+        mod $name {
+            use super::*;
+
+            type Set = $set;
+
+            fn to_set(elems: &[Elem]) -> Set {
+                Set::from_intoiter(get_key, elems.iter().cloned())
+            }
+
+            fn to_oracle(set: &Set) -> HashSet<u8> {
+                set.iter().map(get_key).collect()
+            }
+
+            fn sorted(set: &Set) -> Vec<Elem> {
+                let mut res: Vec<Elem> = set.iter().cloned().collect();
+                res.sort_unstable();
+                res
+            }
+
+            proptest! {
+                #[test]
+                fn set_op_matches_hashset(a in elems(), b in elems()) {
+                    let (a, b) = (to_set(&a), to_set(&b));
+                    let (oa, ob) = (to_oracle(&a), to_oracle(&b));
+
+                    prop_assert_eq!(to_oracle(&a.union(&b)), &oa | &ob);
+                    prop_assert_eq!(to_oracle(&a.intersection(&b)), &oa & &ob);
+                    prop_assert_eq!(to_oracle(&a.difference(&b)), &oa - &ob);
+                    prop_assert_eq!(to_oracle(&a.symmetric_difference(&b)), &oa ^ &ob);
+                }
+
+                #[test]
+                fn set_op_commutative_up_to_key(a in elems(), b in elems()) {
+                    let (a, b) = (to_set(&a), to_set(&b));
+
+                    prop_assert_eq!(a.union(&b), b.union(&a));
+                    prop_assert_eq!(a.intersection(&b), b.intersection(&a));
+                    prop_assert_eq!(a.symmetric_difference(&b), b.symmetric_difference(&a));
+                }
+
+                #[test]
+                fn set_op_associative(a in elems(), b in elems(), c in elems()) {
+                    let (a, b, c) = (to_set(&a), to_set(&b), to_set(&c));
+
+                    // left bias makes them equal element by element, not just by key
+                    prop_assert_eq!(sorted(&a.union(&b).union(&c)), sorted(&a.union(&b.union(&c))));
+                    prop_assert_eq!(
+                        sorted(&a.intersection(&b).intersection(&c)),
+                        sorted(&a.intersection(&b.intersection(&c)))
+                    );
+                    prop_assert_eq!(
+                        a.symmetric_difference(&b).symmetric_difference(&c),
+                        a.symmetric_difference(&b.symmetric_difference(&c))
+                    );
+                }
+
+                #[test]
+                fn set_op_de_morgan(a in elems(), b in elems(), c in elems()) {
+                    let (a, b, c) = (to_set(&a), to_set(&b), to_set(&c));
+
+                    prop_assert_eq!(
+                        sorted(&a.difference(&b.union(&c))),
+                        sorted(&a.difference(&b).intersection(&a.difference(&c)))
+                    );
+                    prop_assert_eq!(
+                        sorted(&a.difference(&b.intersection(&c))),
+                        sorted(&a.difference(&b).union(&a.difference(&c)))
+                    );
+                }
+
+                #[test]
+                fn set_op_bias(a in elems(), b in elems()) {
+                    let (a, b) = (to_set(&a), to_set(&b));
+
+                    for v in a.union(&b).iter() {
+                        prop_assert_eq!(a.get(v).or_else(|| b.get(v)), Some(v));
+                    }
+                    for v in a.union_by(&b, Bias::Right).iter() {
+                        prop_assert_eq!(b.get(v).or_else(|| a.get(v)), Some(v));
+                    }
+                    for v in a.intersection(&b).iter() {
+                        prop_assert_eq!(a.get(v), Some(v));
+                    }
+
+                    let merged = a.intersection_by(&b, Bias::Merge(|l, r| (l.0, l.1.wrapping_add(r.1))));
+                    for v in merged.iter() {
+                        let (l, r) = (a.get(v).unwrap(), b.get(v).unwrap());
+                        prop_assert_eq!(v.1, l.1.wrapping_add(r.1));
+                    }
+                }
+
+                #[test]
+                fn set_op_in_place_matches_owned(a in elems(), b in elems()) {
+                    let (a, b) = (to_set(&a), to_set(&b));
+
+                    let mut res = to_set(&sorted(&a));
+                    res.union_with(to_set(&sorted(&b)));
+                    prop_assert_eq!(sorted(&res), sorted(&a.union(&b)));
+
+                    let mut res = to_set(&sorted(&a));
+                    res.retain_intersection(&b);
+                    prop_assert_eq!(sorted(&res), sorted(&a.intersection(&b)));
+
+                    let mut res = to_set(&sorted(&a));
+                    res.subtract(&b);
+                    prop_assert_eq!(sorted(&res), sorted(&a.difference(&b)));
+
+                    let mut res = to_set(&sorted(&a));
+                    res.symmetric_difference_with(to_set(&sorted(&b)));
+                    prop_assert_eq!(sorted(&res), sorted(&a.symmetric_difference(&b)));
+                }
+
+                #[test]
+                fn bulk_reject_hands_back_duplicates(a in elems(), b in elems()) {
+                    let a = to_set(&a);
+                    let fresh: HashSet<u8> = b.iter().map(get_key).filter(|k| !a.contains_key(k)).collect();
+
+                    let mut res = to_set(&sorted(&a));
+                    res.set_duplicate_policy(DuplicatePolicy::Reject);
+                    let rejected = res.try_extend(b.iter().cloned()).err().unwrap_or_default();
+
+                    prop_assert_eq!(rejected.len(), b.len() - fresh.len());
+                    prop_assert_eq!(to_oracle(&res), &to_oracle(&a) | &fresh);
+                    for v in a.iter() {
+                        prop_assert_eq!(res.get(v), Some(v));
+                    }
+
+                    // extend drops the same elements instead
+                    let mut skipped = to_set(&sorted(&a));
+                    skipped.set_duplicate_policy(DuplicatePolicy::Reject);
+                    skipped.extend(b.iter().cloned());
+                    prop_assert_eq!(sorted(&skipped), sorted(&res));
+                }
+            }
+        }
+    };
+}
+
+set_laws!(key_hash_set, KeyHashSet<Elem, u8>);
+set_laws!(key_index_set, KeyIndexSet<Elem, u8>);
+set_laws!(key_btree_set, KeyBTreeSet<Elem, u8>);
+set_laws!(small_key_set, SmallKeySet<Elem, u8, 4>);
+set_laws!(key_vec_set, KeyVecSet<Elem, u8>);
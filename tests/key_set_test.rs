@@ -1,6 +1,17 @@
+#![allow(
+    clippy::inconsistent_digit_grouping,
+    clippy::derived_hash_with_manual_eq,
+    clippy::assertions_on_constants,
+    clippy::bool_assert_comparison,
+    clippy::println_empty_string
+)]
+
 use std::fmt;
 
-use key_set::{ KeyHashSet, KeySet, GetKeyType, debug_key };
+use key_set::{
+    KeyHashSet, KeySet, GetKeyType, BoxedGetKey, SharedGetKey, Entry, KeyChangePolicy,
+    DuplicatePolicy, InsertOutcome, debug_key
+};
 
 #[test]
 fn create_cutomhashset_basictype() {
@@ -131,16 +142,16 @@ fn set_op_basictype() {
     set2.insert("b");
     set2.insert("e");
 
-    // test union
-    let unioned_set = set1.union(&set2);
+    // test intersection
+    let intersectioned_set = set1.intersection(&set2);
 
     let mut set3 = KeyHashSet::new(debug_key);
     set3.insert("b");
 
-    assert_eq!(unioned_set, set3);
+    assert_eq!(intersectioned_set, set3);
 
-    // test intersection
-    let intersectioned_set =  set1.intersection(&set2);
+    // test union
+    let unioned_set =  set1.union(&set2);
     let mut set4 = KeyHashSet::new(debug_key);
     set4.insert("a");
     set4.insert("b");
@@ -148,8 +159,8 @@ fn set_op_basictype() {
     set4.insert("d");
     set4.insert("e");
 
-    assert_eq!(intersectioned_set, set4);
-    assert_eq!(set1.intersection(&set3), set1);
+    assert_eq!(unioned_set, set4);
+    assert_eq!(set1.union(&set3), set1);
 
     // test difference
     let differenced_set = set1.difference(&set2);
@@ -288,16 +299,16 @@ fn set_op_struct() {
     set2.insert(gen_person_sample("d"));
     set2.insert(gen_person_sample("e"));
 
-    // test union
-    let unioned_set = set1.union(&set2);
+    // test intersection
+    let intersectioned_set = set1.intersection(&set2);
 
     let mut set3 = KeyHashSet::new(debug_key);
     set3.insert(gen_person_sample("b"));
 
-    assert_eq!(unioned_set, set3);
+    assert_eq!(intersectioned_set, set3);
 
-    // test intersection
-    let intersectioned_set =  set1.intersection(&set2);
+    // test union
+    let unioned_set =  set1.union(&set2);
     let mut set4 = KeyHashSet::new(debug_key);
     set4.insert(gen_person_sample("a"));
     set4.insert(gen_person_sample("b"));
@@ -305,8 +316,8 @@ fn set_op_struct() {
     set4.insert(gen_person_sample("d"));
     set4.insert(gen_person_sample("e"));
 
-    assert_eq!(intersectioned_set, set4);
-    assert_eq!(set1.intersection(&set3), set1);
+    assert_eq!(unioned_set, set4);
+    assert_eq!(set1.union(&set3), set1);
 
     // test difference
     let differenced_set = set1.difference(&set2);
@@ -378,4 +389,628 @@ fn extend_set() {
     assert!(set1.contains(&gen_person_sample("a")));
     assert!(set1.contains(&gen_person_sample("b")));
     assert!(set1.contains(&gen_person_sample("c")));
-}
\ No newline at end of file
+}
+
+#[test]
+fn closure_get_key() {
+    let fold_case = true;
+    let get_key = move |name: &&str| {
+        if fold_case { name.to_lowercase() } else { name.to_string() }
+    };
+
+    let mut set1 = KeyHashSet::with_get_key(get_key);
+    set1.insert("Janet");
+    set1.insert("JANET");
+    set1.insert("Byn");
+
+    assert_eq!(set1.len(), 2);
+    assert!(set1.contains(&"janet"));
+
+    // set operation carries the extractor over
+    let set2 = KeyHashSet::from_intoiter_with(get_key, vec!["byn"]);
+    let differenced_set = set1.difference(&set2);
+
+    assert_eq!(differenced_set.len(), 1);
+    assert!(differenced_set.contains(&"jAnEt"));
+}
+
+#[test]
+fn boxed_and_shared_get_key() {
+    let offset = 100;
+
+    let get_key: BoxedGetKey<Person, u32> = Box::new(move |person: &Person| person.id + offset);
+    let mut set1 = KeyHashSet::with_get_key(get_key);
+    set1.insert(gen_person_sample("a"));
+
+    assert!(set1.contains(&gen_person_sample("a")));
+    assert!(!set1.contains(&gen_person_sample("b")));
+
+    let get_key = SharedGetKey::new(move |person: &Person| person.id + offset);
+    let set2 = KeyHashSet::from_intoiter_with(
+        get_key.clone(),
+        vec![gen_person_sample("a"), gen_person_sample("b")]
+    );
+    let set3 = KeyHashSet::from_intoiter_with(get_key, vec![gen_person_sample("b")]);
+
+    assert_eq!(set2.symmetric_difference(&set3).len(), 1);
+
+    // fn pointer still works through the trait constructor
+    let set4: KeyHashSet<Person, u32, SharedGetKey<Person, u32>> = KeySet::new(GET_KEY_FUNC);
+    assert!(set4.is_empty());
+}
+
+#[test]
+fn set_io_by_key() {
+    let get_key_func_byname = |person: &Person| String::from(&person.name);
+    let get_key = get_key_func_byname as GetKeyType<Person, String>;
+
+    let mut set1 = KeyHashSet::new(get_key);
+    set1.insert(gen_person_sample("a"));
+    set1.insert(gen_person_sample("b"));
+    set1.insert(gen_person_sample("d"));
+
+    // borrowed form of the key
+    assert!(set1.contains_key("Janet"));
+    assert!(!set1.contains_key("Kat"));
+
+    match set1.get_by_key("Byn") {
+        Some(v) => assert_eq!(v, &gen_person_sample("b")),
+        None => unreachable!()
+    }
+
+    // test remove
+    assert!(set1.remove_by_key("Janet"));
+    assert!(!set1.contains(&gen_person_sample("a")));
+    assert!(!set1.remove_by_key("Janet"));
+
+    // test take
+    match set1.take_by_key("Jun") {
+        Some(v) => assert_eq!(v, gen_person_sample("d")),
+        None => unreachable!()
+    }
+
+    assert_eq!(set1.len(), 1);
+}
+
+#[test]
+fn set_io_by_key_through_trait() {
+    fn lookup<S: KeySet<Person, u32>>(set: &mut S) {
+        assert!(set.contains_key(&6));
+        assert!(set.get_by_key(&5).is_none());
+        assert!(set.take_by_key(&6).is_some());
+        assert!(!set.remove_by_key(&6));
+    }
+
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.insert(gen_person_sample("b"));
+
+    lookup(&mut set1);
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn entry_api() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.insert(gen_person_sample("a"));
+
+    // or_insert keeps the existing element
+    let mut janet = gen_person_sample("a");
+    janet.phone = 0;
+    assert_eq!(set1.entry(janet.clone()).or_insert().phone, 555_666_7777);
+
+    // or_insert_with only runs on vacant entries
+    let v = set1.entry_by_key(6).or_insert_with(|| gen_person_sample("b"));
+    assert_eq!(v.name, "Byn");
+    assert_eq!(set1.len(), 2);
+
+    // and_modify updates the non-key fields in place
+    set1.entry_by_key(5)
+        .and_modify(|person| person.phone = 1)
+        .or_insert_with(|| unreachable!());
+    assert_eq!(set1.get_by_key(&5).unwrap().phone, 1);
+
+    // insert else replace
+    match set1.entry(gen_person_sample("c")) {
+        Entry::Occupied(_) => unreachable!(),
+        Entry::Vacant(entry) => {
+            assert_eq!(entry.key(), &7);
+            assert_eq!(entry.insert().name, "Janet");
+        }
+    }
+
+    match set1.entry(janet.clone()) {
+        Entry::Occupied(entry) => assert_eq!(entry.replace().phone, 1),
+        Entry::Vacant(_) => unreachable!()
+    }
+    assert_eq!(set1.get_by_key(&5).unwrap().phone, 0);
+
+    match set1.entry_by_key(7) {
+        Entry::Occupied(entry) => assert_eq!(entry.replace_with(|| gen_person_sample("c")).phone, 888_999_0000),
+        Entry::Vacant(_) => unreachable!()
+    }
+
+    // remove
+    match set1.entry_by_key(6) {
+        Entry::Occupied(entry) => assert_eq!(entry.remove().name, "Byn"),
+        Entry::Vacant(_) => unreachable!()
+    }
+    assert!(!set1.contains_key(&6));
+    assert_eq!(set1.len(), 2);
+}
+
+#[test]
+#[should_panic]
+fn entry_rejects_key_mismatch() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+
+    set1.entry_by_key(5).or_insert_with(|| gen_person_sample("b"));
+}
+
+#[test]
+fn entry_modify_keeps_key() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.insert(gen_person_sample("a"));
+    set1.insert(gen_person_sample("b"));
+
+    // a changed key is caught before the element is left under the old one
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = set1.entry_by_key(5).and_modify(|person| person.id = 7);
+    }));
+    assert!(result.is_err());
+    assert!(!set1.contains_key(&5));
+    assert!(set1.get_by_key(&7).is_none());
+
+    // so is one changed by a callback that panics
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = set1.entry(gen_person_sample("b")).and_modify(|person| {
+            person.id = 7;
+            panic!("callback failed");
+        });
+    }));
+    assert!(result.is_err());
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn get_mut_guarded() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.insert(gen_person_sample("a"));
+    set1.insert(gen_person_sample("b"));
+
+    // non-key update
+    set1.get_mut(&gen_person_sample("a")).unwrap().phone = 1;
+    assert_eq!(set1.get_by_key(&5).unwrap().phone, 1);
+    assert!(set1.get_mut_by_key(&0).is_none());
+
+    // key update rehomes the element
+    set1.get_mut_by_key(&5).unwrap().id = 10;
+    assert!(!set1.contains_key(&5));
+    assert_eq!(set1.get_by_key(&10).unwrap().name, "Janet");
+    assert_eq!(set1.len(), 2);
+
+    // reject hands the element back
+    set1.set_key_change_policy(KeyChangePolicy::Reject);
+    let mut guard = set1.get_mut_by_key(&6).unwrap();
+    guard.id = 11;
+    match guard.commit() {
+        Err(rejected) => assert_eq!(rejected.into_inner().name, "Byn"),
+        Ok(_) => unreachable!()
+    }
+    assert_eq!(set1.len(), 1);
+
+    let mut guard = set1.get_mut_by_key(&10).unwrap();
+    guard.name = "Jun".to_string();
+    assert!(guard.commit().is_ok());
+    assert_eq!(set1.get_by_key(&10).unwrap().name, "Jun");
+}
+
+#[test]
+#[should_panic]
+fn get_mut_guarded_panic_policy() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.insert(gen_person_sample("a"));
+    set1.set_key_change_policy(KeyChangePolicy::Panic);
+
+    set1.get_mut_by_key(&5).unwrap().id = 6;
+}
+
+#[test]
+fn get_mut_guarded_never_mismatches() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.insert(gen_person_sample("a"));
+    set1.insert(gen_person_sample("b"));
+
+    // a taken key isn't overwritten under KeepFirst, the element is taken out
+    set1.set_duplicate_policy(DuplicatePolicy::KeepFirst);
+    let mut guard = set1.get_mut_by_key(&5).unwrap();
+    guard.id = 6;
+    assert_eq!(guard.commit().unwrap_err().into_inner().name, "Janet");
+    assert_eq!(set1.len(), 1);
+    assert!(!set1.contains_key(&5));
+    assert_eq!(set1.get_by_key(&6).unwrap().name, "Byn");
+
+    // dropped instead of committed, it is gone too
+    set1.insert(gen_person_sample("a"));
+    set1.get_mut_by_key(&5).unwrap().id = 6;
+    assert_eq!(set1.len(), 1);
+    assert_eq!(set1.get_by_key(&6).unwrap().name, "Byn");
+    set1.insert(gen_person_sample("a"));
+
+    // under KeepLast the displaced element is handed back
+    set1.set_duplicate_policy(DuplicatePolicy::KeepLast);
+    let mut guard = set1.get_mut_by_key(&5).unwrap();
+    guard.id = 6;
+    assert_eq!(guard.commit().unwrap().unwrap().name, "Byn");
+    assert_eq!(set1.len(), 1);
+    assert_eq!(set1.get_by_key(&6).unwrap().name, "Janet");
+
+    // forgetting a guard doesn't lose the element
+    set1.set_key_change_policy(KeyChangePolicy::Reject);
+    set1.get_mut_by_key(&6).unwrap().phone = 7;
+    std::mem::forget(set1.get_mut_by_key(&6).unwrap());
+    assert_eq!(set1.get_by_key(&6).unwrap().phone, 7);
+
+    // a rejected key change leaves no element under the old key
+    set1.get_mut_by_key(&6).unwrap().id = 7;
+    assert!(set1.is_empty());
+    assert!(!set1.contains_key(&6));
+    set1.insert(gen_person_sample("c"));
+    assert_eq!(set1.len(), 1);
+    for person in set1.iter() {
+        assert_eq!(set1.get_by_key(&person.id), Some(person));
+    }
+}
+
+#[test]
+fn get_mut_guarded_panic_takes_element_out() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.insert(gen_person_sample("a"));
+    set1.insert(gen_person_sample("b"));
+    set1.set_key_change_policy(KeyChangePolicy::Panic);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        set1.get_mut_by_key(&5).unwrap().id = 7;
+    }));
+    assert!(result.is_err());
+    assert_eq!(set1.len(), 1);
+    assert!(!set1.contains_key(&5));
+    assert!(!set1.contains(&gen_person_sample("c")));
+
+    // a panic raised while the guard is borrowed takes it out as well
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut guard = set1.get_mut_by_key(&6).unwrap();
+        guard.id = 7;
+        panic!("while holding the guard");
+    }));
+    assert!(result.is_err());
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn insert_outcome() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    assert_eq!(set1.insert(gen_person_sample("a")), InsertOutcome::Inserted);
+
+    let mut janet = gen_person_sample("a");
+    janet.phone = 0;
+
+    // KeepLast is the default
+    match set1.insert(janet.clone()) {
+        InsertOutcome::Replaced(old) => assert_eq!(old.phone, 555_666_7777),
+        _ => unreachable!()
+    }
+    assert_eq!(set1.get_by_key(&5).unwrap().phone, 0);
+
+    // try_insert never replaces
+    assert_eq!(set1.try_insert(gen_person_sample("a")).unwrap_err().phone, 555_666_7777);
+    assert_eq!(set1.try_insert(gen_person_sample("b")).unwrap().name, "Byn");
+
+    set1.set_duplicate_policy(DuplicatePolicy::KeepFirst);
+    assert_eq!(set1.insert(gen_person_sample("a")).into_displaced().unwrap().phone, 555_666_7777);
+    assert_eq!(set1.get_by_key(&5).unwrap().phone, 0);
+
+    set1.set_duplicate_policy(DuplicatePolicy::Reject);
+    assert!(matches!(set1.insert(gen_person_sample("b")), InsertOutcome::Rejected(_)));
+    assert!(set1.insert(gen_person_sample("c")).is_fresh());
+
+    set1.set_duplicate_policy(DuplicatePolicy::Merge(|old, new| old.phone += new.phone));
+    assert_eq!(set1.insert(gen_person_sample("c")), InsertOutcome::Merged);
+    assert_eq!(set1.get_by_key(&7).unwrap().phone, 2 * 888_999_0000);
+    assert_eq!(set1.len(), 3);
+}
+
+#[test]
+fn duplicate_policy_bulk_insert() {
+    let v = vec![gen_person_sample("a"), gen_person_sample("b"), gen_person_sample("a")];
+
+    let mut set1 = KeyHashSet::from_intoiter_with_policy(
+        GET_KEY_FUNC,
+        DuplicatePolicy::Merge(|old, new| old.name.push_str(&new.name)),
+        v
+    );
+    assert_eq!(set1.get_by_key(&5).unwrap().name, "JanetJanet");
+
+    set1.set_duplicate_policy(DuplicatePolicy::KeepFirst);
+    set1.extend(vec![gen_person_sample("a"), gen_person_sample("c")]);
+    assert_eq!(set1.get_by_key(&5).unwrap().name, "JanetJanet");
+    assert_eq!(set1.len(), 3);
+}
+
+#[test]
+fn duplicate_policy_reject_bulk_insert() {
+    let mut set1 = KeyHashSet::new(GET_KEY_FUNC);
+    set1.set_duplicate_policy(DuplicatePolicy::Reject);
+
+    // extend skips the refused elements
+    set1.extend(vec![gen_person_sample("a"), gen_person_sample("a")]);
+    assert_eq!(set1.len(), 1);
+
+    // try_extend hands them back
+    let mut janet = gen_person_sample("a");
+    janet.phone = 0;
+    match set1.try_extend(vec![gen_person_sample("b"), janet]) {
+        Err(rejected) => assert_eq!(rejected.iter().map(|person| person.phone).collect::<Vec<u64>>(), vec![0]),
+        Ok(()) => unreachable!()
+    }
+    assert_eq!(set1.len(), 2);
+    assert_eq!(set1.get_by_key(&5).unwrap().phone, 555_666_7777);
+    assert!(set1.try_extend(vec![gen_person_sample("c")]).is_ok());
+}
+
+#[test]
+fn set_op_iter() {
+    let set1 = KeyHashSet::from_intoiter(
+        GET_KEY_FUNC,
+        vec![gen_person_sample("a"), gen_person_sample("b"), gen_person_sample("c")]
+    );
+    let set2 = KeyHashSet::from_intoiter(
+        GET_KEY_FUNC,
+        vec![gen_person_sample("b"), gen_person_sample("d"), gen_person_sample("e")]
+    );
+
+    fn sorted_ids<'a>(iter: impl Iterator<Item=&'a Person>) -> Vec<u32> {
+        let mut ids: Vec<u32> = iter.map(|person| person.id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    assert_eq!(sorted_ids(set1.intersection_iter(&set2)), vec![6]);
+    assert_eq!(sorted_ids(set1.union_iter(&set2)), vec![5, 6, 7, 8, 9]);
+    assert_eq!(sorted_ids(set1.difference_iter(&set2)), vec![5, 7]);
+    assert_eq!(sorted_ids(set1.symmetric_difference_iter(&set2)), vec![5, 7, 8, 9]);
+
+    // iterators are cloneable and borrow instead of cloning elements
+    let mut iter = set2.difference_iter(&set1);
+    assert_eq!(iter.clone().count(), 2);
+    assert!(iter.all(|person| !set1.contains(person)));
+}
+
+#[test]
+fn set_op_operator() {
+    let set1 = KeyHashSet::from_intoiter(debug_key, vec!["a", "b", "c"]);
+    let set2 = KeyHashSet::from_intoiter(debug_key, vec!["b", "d", "e"]);
+
+    assert_eq!(&set1 | &set2, set1.union(&set2));
+    assert_eq!(&set1 & &set2, set1.intersection(&set2));
+    assert_eq!(&set1 - &set2, set1.difference(&set2));
+    assert_eq!(&set1 ^ &set2, set1.symmetric_difference(&set2));
+
+    let set3 = KeyHashSet::from_intoiter(debug_key, vec!["a"]);
+    assert_eq!(&(&set1 | &set2) - &set3, KeyHashSet::from_intoiter(debug_key, vec!["b", "c", "d", "e"]));
+}
+
+#[test]
+fn set_op_assign_operator() {
+    let set2 = KeyHashSet::from_intoiter(GET_KEY_FUNC, vec![gen_person_sample("b"), gen_person_sample("d")]);
+
+    let mut set1 = KeyHashSet::from_intoiter(GET_KEY_FUNC, vec![gen_person_sample("a"), gen_person_sample("b")]);
+    set1.get_mut_by_key(&6).unwrap().phone = 0;
+
+    // the left side keeps its own elements
+    set1 |= &set2;
+    assert_eq!(set1.len(), 3);
+    assert_eq!(set1.get_by_key(&6).unwrap().phone, 0);
+
+    set1 -= &set2;
+    assert_eq!(set1.len(), 1);
+    assert!(set1.contains_key(&5));
+
+    set1 ^= &set2;
+    assert_eq!(set1.len(), 3);
+
+    set1 &= &set2;
+    assert_eq!(set1, set2);
+}
+
+#[test]
+fn set_op_in_place() {
+    // neither Clone nor Debug
+    struct Handle {
+        id: u32,
+        buf: Vec<u8>,
+    }
+
+    fn handles(ids: &[u32]) -> KeyHashSet<Handle, u32> {
+        KeyHashSet::from_intoiter(
+            |handle: &Handle| handle.id,
+            ids.iter().map(|&id| Handle { id, buf: vec![id as u8] })
+        )
+    }
+
+    fn sorted_ids(set: &KeyHashSet<Handle, u32>) -> Vec<u32> {
+        let mut ids: Vec<u32> = set.iter().map(|handle| handle.id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    let mut set1 = handles(&[1, 2, 3]);
+    let mut set2 = handles(&[3, 4]);
+    set2.get_mut_by_key(&3).unwrap().buf.clear();
+
+    // self keeps its own element on a shared key
+    set1.union_with(set2);
+    assert_eq!(sorted_ids(&set1), vec![1, 2, 3, 4]);
+    assert_eq!(set1.get_by_key(&3).unwrap().buf, vec![3]);
+
+    set1.retain_intersection(&handles(&[2, 3, 4, 5]));
+    assert_eq!(sorted_ids(&set1), vec![2, 3, 4]);
+
+    set1.subtract(&handles(&[4]));
+    assert_eq!(sorted_ids(&set1), vec![2, 3]);
+
+    set1.symmetric_difference_with(handles(&[3, 6]));
+    assert_eq!(sorted_ids(&set1), vec![2, 6]);
+}
+
+#[test]
+fn iter_keys() {
+    let set1 = KeyHashSet::from_intoiter(
+        GET_KEY_FUNC,
+        vec![gen_person_sample("a"), gen_person_sample("b"), gen_person_sample("c")]
+    );
+
+    let iter = set1.iter();
+    assert_eq!(iter.len(), 3);
+    assert_eq!(iter.clone().count(), 3);
+
+    let mut keys: Vec<u32> = set1.keys().cloned().collect();
+    keys.sort_unstable();
+    assert_eq!(keys, vec![5, 6, 7]);
+
+    for (key, person) in set1.iter_with_keys() {
+        assert_eq!(*key, person.id);
+    }
+
+    let mut n = 0;
+    for person in &set1 {
+        assert!(set1.contains(person));
+        n += 1;
+    }
+    assert_eq!(n, set1.len());
+}
+
+#[test]
+fn retain_drain_extract_if() {
+    let samples = ["a", "b", "c", "d", "e"];
+    let mut set1 = KeyHashSet::from_intoiter(GET_KEY_FUNC, samples.iter().map(|x| gen_person_sample(x)));
+
+    // test retain
+    set1.retain(|person| person.id != 9);
+    assert_eq!(set1.len(), 4);
+    assert!(!set1.contains_key(&9));
+
+    // test extract_if
+    let mut extracted: Vec<u32> = set1.extract_if(|person| person.name == "Janet")
+        .map(|person| person.id)
+        .collect();
+    extracted.sort_unstable();
+    assert_eq!(extracted, vec![5, 7]);
+    assert_eq!(set1.len(), 2);
+
+    // test drain
+    let drain = set1.drain();
+    assert_eq!(drain.len(), 2);
+    let mut drained: Vec<u32> = drain.map(|person| person.id).collect();
+    drained.sort_unstable();
+    assert_eq!(drained, vec![6, 8]);
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn capacity_management() {
+    let mut set1 = KeyHashSet::with_capacity(GET_KEY_FUNC, 100);
+    assert!(set1.capacity() >= 100);
+    assert!(set1.is_empty());
+
+    set1.insert(gen_person_sample("a"));
+    set1.shrink_to_fit();
+    assert!(set1.capacity() >= 1);
+
+    set1.reserve(10);
+    assert!(set1.capacity() >= 11);
+
+    assert!(set1.try_reserve(10).is_ok());
+    assert!(set1.try_reserve(usize::MAX).is_err());
+    assert!(set1.contains_key(&5));
+
+    // bulk loading reserves from the size hint
+    let set2 = KeyHashSet::from_intoiter(debug_key, 0..1000);
+    assert_eq!(set2.len(), 1000);
+    assert!(set2.capacity() >= 1000);
+}
+
+#[test]
+fn custom_hasher() {
+    use std::hash::{ BuildHasher, Hasher };
+
+    // no `Default`, a hasher that has to be passed in
+    #[derive(Clone)]
+    struct SeededState {
+        seed: u64,
+    }
+
+    struct SeededHasher {
+        state: u64,
+    }
+
+    impl Hasher for SeededHasher {
+        fn finish(&self) -> u64 {
+            self.state
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for b in bytes {
+                self.state = self.state.rotate_left(8) ^ u64::from(*b);
+            }
+        }
+    }
+
+    impl BuildHasher for SeededState {
+        type Hasher = SeededHasher;
+
+        fn build_hasher(&self) -> SeededHasher {
+            SeededHasher { state: self.seed }
+        }
+    }
+
+    let mut set1 = KeyHashSet::with_hasher(GET_KEY_FUNC, SeededState { seed: 42 });
+    set1.insert(gen_person_sample("a"));
+    set1.insert(gen_person_sample("b"));
+
+    let mut set2 = KeyHashSet::with_capacity_and_hasher(GET_KEY_FUNC, 8, SeededState { seed: 42 });
+    set2.insert(gen_person_sample("b"));
+    set2.insert(gen_person_sample("c"));
+    assert!(set2.capacity() >= 8);
+
+    assert!(set1.contains_key(&5));
+    assert!(set1.get_by_key(&6).is_some());
+    assert!(set1.contains(&gen_person_sample("a")));
+    assert_eq!(set1.get(&gen_person_sample("b")).unwrap().name, "Byn");
+    assert_eq!(set1.iter().count(), 2);
+    assert!(set2.remove(&gen_person_sample("c")));
+    assert!(set2.take(&gen_person_sample("c")).is_none());
+    set2.insert(gen_person_sample("c"));
+
+    // set operations clone the hasher of the left side
+    let unioned_set = set1.union(&set2);
+    assert_eq!(unioned_set.len(), 3);
+    assert_eq!(unioned_set.hasher().seed, 42);
+    assert_eq!((&set1 - &set2).hasher().seed, 42);
+
+    set1 -= &set2;
+    assert_eq!(set1.len(), 1);
+    set1.symmetric_difference_with(set2);
+    assert_eq!(set1.len(), 3);
+    set1.retain(|person| person.id != 7);
+    assert_eq!(set1.drain().count(), 2);
+
+    // the fixed-seed std hasher iterates in the same order every run
+    type FixedState = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+
+    let fixed_ids = || {
+        let mut set3 = KeyHashSet::with_hasher(GET_KEY_FUNC, FixedState::default());
+        set3.extend(["a", "b", "c", "d", "e"].iter().map(|x| gen_person_sample(x)));
+        set3.iter().map(|person| person.id).collect::<Vec<u32>>()
+    };
+    assert_eq!(fixed_ids(), fixed_ids());
+}
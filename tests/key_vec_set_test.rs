@@ -0,0 +1,210 @@
+#![allow(clippy::inconsistent_digit_grouping)]
+
+mod common;
+
+use key_set::{ KeyVecSet, KeySet, DuplicatePolicy, InsertOutcome, Bias, debug_key };
+
+use common::{ Person, GET_KEY_FUNC, gen_person_sample, ids };
+
+fn gen_person_set(identifiers: &[&str]) -> KeyVecSet<Person, u32> {
+    KeyVecSet::from_intoiter(GET_KEY_FUNC, identifiers.iter().map(|x| gen_person_sample(x)))
+}
+
+#[test]
+fn create_keyvecset_basictype() {
+    let mut myset = KeyVecSet::new(debug_key);
+
+    myset.insert("c");
+    myset.insert("a");
+    myset.insert("b");
+
+    assert!(myset.contains(&"a"));
+    assert!(!myset.contains(&"d"));
+    assert_eq!(myset.iter().cloned().collect::<Vec<&str>>(), vec!["a", "b", "c"]);
+
+    // test remove
+    myset.remove(&"a");
+    myset.remove(&"c");
+    assert!(!myset.contains(&"a"));
+    assert!(myset.contains(&"b"));
+    assert!(!myset.contains(&"c"));
+}
+
+#[test]
+fn bulk_build_sorts_and_dedups() {
+    let mut janet = gen_person_sample("a");
+    janet.phone = 0;
+
+    // the last duplicate wins by default
+    let samples = vec![gen_person_sample("c"), gen_person_sample("a"), gen_person_sample("b"), janet.clone()];
+    let set1 = KeyVecSet::from_intoiter(GET_KEY_FUNC, samples.clone());
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7]);
+    assert_eq!(set1.get_by_key(&5).unwrap().phone, 0);
+
+    let set2 = KeyVecSet::from_intoiter_with_policy(GET_KEY_FUNC, DuplicatePolicy::KeepFirst, samples.clone());
+    assert_eq!(set2.get_by_key(&5).unwrap().phone, 555_666_7777);
+
+    let set3 = KeyVecSet::from_intoiter_with_policy(
+        GET_KEY_FUNC,
+        DuplicatePolicy::Merge(|old, new| old.phone += new.phone + 1),
+        samples
+    );
+    assert_eq!(set3.get_by_key(&5).unwrap().phone, 555_666_7778);
+
+    // extend keeps the elements held before the new ones in the same run
+    let mut set4 = gen_person_set(&["e", "a"]);
+    set4.set_duplicate_policy(DuplicatePolicy::KeepFirst);
+    set4.extend(vec![gen_person_sample("d"), janet]);
+    assert_eq!(ids(set4.iter()), vec![5, 8, 9]);
+    assert_eq!(set4.get_by_key(&5).unwrap().phone, 555_666_7777);
+}
+
+#[test]
+fn bulk_build_rejects_duplicate() {
+    let set1 = KeyVecSet::from_intoiter_with_policy(
+        GET_KEY_FUNC,
+        DuplicatePolicy::Reject,
+        vec![gen_person_sample("c"), gen_person_sample("a"), gen_person_sample("c")]
+    );
+    assert_eq!(ids(set1.iter()), vec![5, 7]);
+
+    // the set is left whole, the refused elements come back in key order
+    let mut set2 = KeyVecSet::from_intoiter(debug_key, vec![1, 2, 3]);
+    set2.set_duplicate_policy(DuplicatePolicy::Reject);
+
+    assert_eq!(set2.try_extend(vec![4, 3, 1, 0]), Err(vec![1, 3]));
+    assert_eq!(set2.iter().cloned().collect::<Vec<i32>>(), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn set_op_struct() {
+    let set1 = gen_person_set(&["c", "a", "b"]);
+    let set2 = gen_person_set(&["e", "b", "d"]);
+
+    assert_eq!(ids(set1.intersection(&set2).iter()), vec![6]);
+    assert_eq!(ids(set1.union(&set2).iter()), vec![5, 6, 7, 8, 9]);
+    assert_eq!(ids(set1.difference(&set2).iter()), vec![5, 7]);
+    assert_eq!(ids(set1.symmetric_difference(&set2).iter()), vec![5, 7, 8, 9]);
+
+    let mut byn = gen_person_sample("b");
+    byn.phone = 0;
+    let set3 = KeyVecSet::from_intoiter(GET_KEY_FUNC, vec![byn]);
+
+    assert_eq!(set1.union(&set3).get_by_key(&6).unwrap().phone, 222_333_4444);
+    assert_eq!(set1.union_by(&set3, Bias::Right).get_by_key(&6).unwrap().phone, 0);
+}
+
+#[test]
+fn set_io_by_key() {
+    let get_key = |person: &Person| String::from(&person.name);
+    let mut set1 = KeyVecSet::with_get_key(get_key);
+    set1.extend(vec![gen_person_sample("a"), gen_person_sample("b"), gen_person_sample("d")]);
+
+    // borrowed form of the key
+    assert!(set1.contains_key("Janet"));
+    assert!(!set1.contains_key("Kat"));
+    assert_eq!(set1.get_by_key("Byn"), Some(&gen_person_sample("b")));
+    assert_eq!(set1.rank("Janet"), Ok(1));
+    assert_eq!(set1.rank("Kat"), Err(3));
+
+    assert!(set1.remove_by_key("Janet"));
+    assert!(!set1.remove_by_key("Janet"));
+    assert_eq!(set1.take_by_key("Jun"), Some(gen_person_sample("d")));
+    assert_eq!(set1.len(), 1);
+}
+
+#[test]
+fn insert_outcome() {
+    let mut set1 = gen_person_set(&["a", "e"]);
+
+    let mut janet = gen_person_sample("a");
+    janet.phone = 0;
+
+    match set1.insert(janet) {
+        InsertOutcome::Replaced(old) => assert_eq!(old.phone, 555_666_7777),
+        _ => unreachable!()
+    }
+    assert_eq!(set1.first().unwrap().phone, 0);
+
+    // inserted in key order
+    assert!(set1.try_insert(gen_person_sample("a")).is_err());
+    assert_eq!(set1.try_insert(gen_person_sample("c")).unwrap().id, 7);
+    assert_eq!(set1.insert(gen_person_sample("b")), InsertOutcome::Inserted);
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7, 9]);
+
+    set1.set_duplicate_policy(DuplicatePolicy::Merge(|old, new| old.phone += new.phone));
+    assert_eq!(set1.insert(gen_person_sample("c")), InsertOutcome::Merged);
+    assert_eq!(set1.get_by_key(&7).unwrap().phone, 2 * 888_999_0000);
+}
+
+#[test]
+fn ordered_access() {
+    let mut set1 = gen_person_set(&["c", "a", "e", "b", "d"]);
+
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7, 8, 9]);
+    assert_eq!(ids(set1.iter().rev()), vec![9, 8, 7, 6, 5]);
+    assert_eq!(set1.keys().cloned().collect::<Vec<u32>>(), vec![5, 6, 7, 8, 9]);
+    assert_eq!(set1.as_slice()[0].0, 5);
+
+    // test range
+    assert_eq!(ids(set1.range(6..8)), vec![6, 7]);
+    assert_eq!(ids(set1.range(7..)), vec![7, 8, 9]);
+    assert_eq!(ids(set1.range(..=6).rev()), vec![6, 5]);
+    assert_eq!(set1.range(10..).len(), 0);
+
+    // test index
+    assert_eq!(set1[1].id, 6);
+    assert_eq!(set1.get_index(4).unwrap().id, 9);
+    assert!(set1.get_index(5).is_none());
+    assert_eq!(set1.first().unwrap().id, 5);
+    assert_eq!(set1.pop_last().unwrap().id, 9);
+    assert_eq!(set1.last().unwrap().id, 8);
+}
+
+#[test]
+fn retain_drain_extract_if() {
+    let mut set1 = gen_person_set(&["a", "b", "c", "d", "e"]);
+
+    set1.retain(|person| person.id != 9);
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7, 8]);
+
+    let extracted: Vec<u32> = set1.extract_if(|person| person.name == "Janet")
+        .map(|person| person.id)
+        .collect();
+    assert_eq!(extracted, vec![5, 7]);
+    assert_eq!(ids(set1.iter()), vec![6, 8]);
+
+    // dropped early, the unvisited elements stay
+    assert_eq!(set1.extract_if(|_| true).next().map(|person| person.id), Some(6));
+    assert_eq!(ids(set1.iter()), vec![8]);
+
+    let drain = set1.drain();
+    assert_eq!(drain.len(), 1);
+    assert_eq!(drain.map(|person| person.id).collect::<Vec<u32>>(), vec![8]);
+    assert!(set1.is_empty());
+}
+
+#[test]
+fn set_op_in_place() {
+    let mut set1 = gen_person_set(&["c", "a", "b"]);
+
+    let mut byn = gen_person_sample("b");
+    byn.phone = 0;
+    let mut set2 = gen_person_set(&["e"]);
+    set2.insert(byn);
+
+    // self keeps its own element on a shared key
+    set1.union_with(set2);
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7, 9]);
+    assert_eq!(set1.get_by_key(&6).unwrap().phone, 222_333_4444);
+
+    set1.retain_intersection(&gen_person_set(&["a", "b", "c"]));
+    assert_eq!(ids(set1.iter()), vec![5, 6, 7]);
+
+    set1.subtract(&gen_person_set(&["a"]));
+    assert_eq!(ids(set1.iter()), vec![6, 7]);
+
+    set1.symmetric_difference_with(gen_person_set(&["d", "c"]));
+    assert_eq!(ids(set1.iter()), vec![6, 8]);
+    assert_eq!(set1.into_iter().map(|person| person.id).collect::<Vec<u32>>(), vec![6, 8]);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::sync::{ Arc, Mutex };
+use std::thread;
+
+use key_set::{ LruKeySet, Evicted, debug_key };
+
+use common::{ Person, GET_KEY_FUNC, person, ids };
+
+#[test]
+fn evict_least_recently_used() {
+    let mut set = LruKeySet::new(GET_KEY_FUNC, 3);
+
+    assert_eq!(set.insert(person(1, "a")), None);
+    assert_eq!(set.insert(person(2, "b")), None);
+    assert_eq!(set.insert(person(3, "c")), None);
+    assert_eq!(ids(&set), vec![3, 2, 1]);
+
+    // overflow evicts the oldest
+    assert_eq!(set.insert(person(4, "d")), Some(Evicted::Overflow(person(1, "a"))));
+    assert_eq!(ids(&set), vec![4, 3, 2]);
+    assert_eq!(set.len(), 3);
+
+    // a known key is replaced and refreshed, nothing is evicted
+    let replaced = set.insert(person(2, "b2")).unwrap();
+    assert_eq!(replaced, Evicted::Replaced(person(2, "b")));
+    assert_eq!(replaced.into_inner().name, "b");
+    assert_eq!(ids(&set), vec![2, 4, 3]);
+
+    assert_eq!(set.insert(person(5, "e")).unwrap().into_inner().id, 3);
+    assert_eq!(set.peek_lru().unwrap().id, 4);
+}
+
+#[test]
+fn reads_refresh_recency() {
+    let mut set = LruKeySet::new(GET_KEY_FUNC, 3);
+    set.insert(person(1, "a"));
+    set.insert(person(2, "b"));
+    set.insert(person(3, "c"));
+
+    assert_eq!(set.get_by_key(&1).unwrap().name, "a");
+    assert!(set.contains(&person(2, "")));
+    assert_eq!(ids(&set), vec![2, 1, 3]);
+
+    // peek leaves the order alone
+    assert_eq!(set.peek_by_key(&3).unwrap().name, "c");
+    assert!(set.peek(&person(9, "")).is_none());
+    assert_eq!(ids(&set), vec![2, 1, 3]);
+
+    assert_eq!(set.insert(person(4, "d")).unwrap().into_inner().id, 3);
+
+    // without refresh_on_read only insert counts as a use
+    let mut set = LruKeySet::new(GET_KEY_FUNC, 2).with_refresh_on_read(false);
+    assert!(!set.refresh_on_read());
+    set.insert(person(1, "a"));
+    set.insert(person(2, "b"));
+    assert!(set.contains_key(&1));
+    assert_eq!(set.get(&person(1, "")).unwrap().name, "a");
+    assert_eq!(set.insert(person(3, "c")).unwrap().into_inner().id, 1);
+}
+
+#[test]
+fn eviction_callback() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let sink = log.clone();
+
+    let mut set = LruKeySet::new(GET_KEY_FUNC, 2)
+        .with_on_evict(move |person: Person| sink.lock().unwrap().push(person.id));
+
+    set.insert(person(1, "a"));
+    set.insert(person(2, "b"));
+    // the callback takes the element instead of the return value
+    assert_eq!(set.insert(person(3, "c")), None);
+    assert_eq!(set.insert(person(4, "d")), None);
+    assert_eq!(*log.lock().unwrap(), vec![1, 2]);
+
+    // replacement isn't an eviction
+    assert!(matches!(set.insert(person(4, "d2")), Some(Evicted::Replaced(_))));
+    assert_eq!(*log.lock().unwrap(), vec![1, 2]);
+
+    // neither is an explicit removal
+    assert_eq!(set.pop_lru().unwrap().id, 3);
+    assert!(set.remove(&person(4, "")));
+    assert_eq!(*log.lock().unwrap(), vec![1, 2]);
+    assert!(set.is_empty());
+}
+
+#[test]
+fn shared_between_threads() {
+    let evicted = Arc::new(Mutex::new(0));
+    let sink = evicted.clone();
+
+    let seen = Mutex::new(
+        LruKeySet::new(GET_KEY_FUNC, 8).with_on_evict(move |_| *sink.lock().unwrap() += 1)
+    );
+
+    thread::scope(|s| {
+        for t in 0..4 {
+            let seen = &seen;
+            s.spawn(move || {
+                for id in 0..8 {
+                    seen.lock().unwrap().insert(person(t * 8 + id, "m"));
+                }
+            });
+        }
+    });
+
+    assert_eq!(seen.lock().unwrap().len(), 8);
+    assert_eq!(*evicted.lock().unwrap(), 24);
+}
+
+#[test]
+fn remove_and_resize() {
+    let mut set = LruKeySet::new(debug_key, 4);
+    for x in 0..4 {
+        set.insert(x);
+    }
+
+    assert!(set.remove_by_key("1"));
+    assert!(!set.remove_by_key("1"));
+    assert_eq!(set.take_by_key("3"), Some(3));
+    assert_eq!(set.iter().cloned().collect::<Vec<i32>>(), vec![2, 0]);
+
+    // the freed slots are reused
+    set.insert(7);
+    set.insert(8);
+    assert_eq!(set.iter().cloned().collect::<Vec<i32>>(), vec![8, 7, 2, 0]);
+
+    assert_eq!(set.set_capacity(2), vec![0, 2]);
+    assert_eq!(set.capacity(), 2);
+    assert_eq!((&set).into_iter().len(), 2);
+
+    assert_eq!(set.pop_lru(), Some(7));
+    assert_eq!(set.pop_lru(), Some(8));
+    assert_eq!(set.pop_lru(), None);
+
+    set.insert(1);
+    set.clear();
+    assert!(set.is_empty());
+    assert!(set.peek_lru().is_none());
+    set.insert(2);
+    assert_eq!(set.len(), 1);
+}
+
+#[test]
+#[should_panic]
+fn zero_capacity() {
+    LruKeySet::new(GET_KEY_FUNC, 0);
+}
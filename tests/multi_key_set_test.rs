@@ -0,0 +1,133 @@
+use key_set::{ MultiKeySet, IndexHandle };
+
+#[derive(Clone, Debug, PartialEq)]
+struct Person {
+    id: u32,
+    email: String,
+    city: String,
+}
+
+fn person(id: u32, email: &str, city: &str) -> Person {
+    Person { id, email: email.to_string(), city: city.to_string() }
+}
+
+struct Indexes {
+    id: IndexHandle<u32>,
+    email: IndexHandle<String>,
+    city: IndexHandle<String>,
+}
+
+fn gen_person_set() -> (MultiKeySet<Person>, Indexes) {
+    let mut set = MultiKeySet::new();
+    let indexes = Indexes {
+        id: set.with_unique_index("id", |person: &Person| person.id),
+        email: set.with_unique_index("email", |person: &Person| person.email.clone()),
+        city: set.with_index("city", |person: &Person| person.city.clone()),
+    };
+
+    set.insert(person(5, "janet@x.org", "Oslo")).unwrap();
+    set.insert(person(6, "byn@x.org", "Rome")).unwrap();
+    set.insert(person(7, "jun@x.org", "Oslo")).unwrap();
+
+    (set, indexes)
+}
+
+fn ids<'a>(iter: impl Iterator<Item=&'a Person>) -> Vec<u32> {
+    let mut ids: Vec<u32> = iter.map(|person| person.id).collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[test]
+fn lookup_by_any_index() {
+    let (set, ix) = gen_person_set();
+
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.index_names().collect::<Vec<&str>>(), vec!["id", "email", "city"]);
+
+    assert_eq!(set.get_by(&ix.id, &6).unwrap().email, "byn@x.org");
+    assert_eq!(set.get_by(&ix.email, "jun@x.org").unwrap().id, 7);
+    assert!(set.get_by(&ix.id, &8).is_none());
+
+    // non-unique, in insertion order
+    assert_eq!(set.get_all_by(&ix.city, "Oslo").map(|p| p.id).collect::<Vec<u32>>(), vec![5, 7]);
+    assert_eq!(set.count_by(&ix.city, "Oslo"), 2);
+    assert!(set.contains_key_by(&ix.city, "Rome"));
+    assert!(!set.contains_key_by(&ix.city, "Lima"));
+
+    assert_eq!(ids(set.iter()), vec![5, 6, 7]);
+}
+
+#[test]
+fn unique_violation() {
+    let (mut set, ix) = gen_person_set();
+
+    // an email clash rejects the element even though its id is new
+    let err = set.insert(person(8, "byn@x.org", "Lima")).unwrap_err();
+    assert_eq!(err.index(), "email");
+    assert_eq!(err.to_string(), "the key is already held by the unique index \"email\"");
+    assert_eq!(err.into_inner().id, 8);
+
+    // nothing was indexed
+    assert!(set.get_by(&ix.id, &8).is_none());
+    assert!(!set.contains_key_by(&ix.city, "Lima"));
+    assert_eq!(set.len(), 3);
+
+    assert_eq!(set.insert(person(5, "other@x.org", "Lima")).unwrap_err().index(), "id");
+    assert!(set.insert(person(8, "kat@x.org", "Oslo")).is_ok());
+    assert_eq!(set.count_by(&ix.city, "Oslo"), 3);
+}
+
+#[test]
+fn remove_cascades() {
+    let (mut set, ix) = gen_person_set();
+
+    let janet = set.take_by(&ix.email, "janet@x.org").unwrap();
+    assert_eq!(janet.id, 5);
+    assert!(set.get_by(&ix.id, &5).is_none());
+    assert_eq!(ids(set.get_all_by(&ix.city, "Oslo")), vec![7]);
+
+    // the freed keys and slot can be used again
+    set.insert(person(9, "janet@x.org", "Rome")).unwrap();
+    assert_eq!(set.get_by(&ix.email, "janet@x.org").unwrap().id, 9);
+
+    let romans = set.remove_all_by(&ix.city, "Rome");
+    assert_eq!(ids(romans.iter()), vec![6, 9]);
+    assert!(set.get_by(&ix.id, &6).is_none());
+    assert!(set.get_by(&ix.email, "janet@x.org").is_none());
+    assert!(set.take_by(&ix.id, &6).is_none());
+
+    assert_eq!(ids(set.iter()), vec![7]);
+    assert_eq!(set.iter().len(), 1);
+}
+
+#[test]
+fn index_added_later() {
+    let mut set = MultiKeySet::new();
+    set.with_unique_index("id", |person: &Person| person.id);
+    set.insert(person(5, "janet@x.org", "Oslo")).unwrap();
+    set.insert(person(6, "byn@x.org", "Oslo")).unwrap();
+
+    let city = set.with_index("city", |person: &Person| person.city.clone());
+    assert_eq!(set.count_by(&city, "Oslo"), 2);
+}
+
+#[test]
+#[should_panic]
+fn unique_index_added_over_duplicates() {
+    let mut set = MultiKeySet::new();
+    set.with_unique_index("id", |person: &Person| person.id);
+    set.insert(person(5, "janet@x.org", "Oslo")).unwrap();
+    set.insert(person(6, "byn@x.org", "Oslo")).unwrap();
+
+    set.with_unique_index("city", |person: &Person| person.city.clone());
+}
+
+#[test]
+#[should_panic]
+fn lookup_with_handle_of_another_set() {
+    let (set, _) = gen_person_set();
+    let (_, other) = gen_person_set();
+
+    set.get_by(&other.id, &5);
+}
@@ -0,0 +1,256 @@
+use std::collections::{ HashMap, HashSet };
+use std::hash::{ Hash, Hasher };
+
+use proptest::prelude::*;
+
+use key_set::{ PersistentKeySet, GetKeyType, Bias, debug_key };
+
+#[derive(Clone, Debug, PartialEq)]
+struct Config {
+    name: &'static str,
+    value: u32,
+}
+
+fn config(name: &'static str, value: u32) -> Config {
+    Config { name, value }
+}
+
+static GET_KEY_FUNC: GetKeyType<Config, &'static str> = |config: &Config| config.name;
+
+fn names(set: &PersistentKeySet<Config, &'static str>) -> Vec<&'static str> {
+    let mut res: Vec<&'static str> = set.keys().cloned().collect();
+    res.sort_unstable();
+    res
+}
+
+#[test]
+fn versions_share_structure() {
+    let v1 = PersistentKeySet::from_intoiter(GET_KEY_FUNC, vec![config("a", 1), config("b", 2)]);
+    let v2 = v1.insert(config("c", 3));
+    let v3 = v2.insert(config("a", 10)).remove(&config("b", 0));
+
+    // old versions are untouched
+    assert_eq!(v1.len(), 2);
+    assert!(!v1.contains_key("c"));
+    assert_eq!(v2.len(), 3);
+    assert_eq!(v2.get_by_key("a").unwrap().value, 1);
+
+    assert_eq!(v3.len(), 2);
+    assert_eq!(v3.get(&config("a", 0)).unwrap().value, 10);
+    assert!(!v3.contains(&config("b", 0)));
+    assert!(v3.contains_key("c"));
+
+    // clone is the same version
+    let v4 = v3.clone();
+    assert!(v4.ptr_eq(&v3));
+    assert!(!v4.ptr_eq(&v2));
+
+    // a miss gives back an equal set
+    let v5 = v3.remove_by_key("z");
+    assert_eq!(v5, v3);
+    assert_eq!(v3.remove_by_key("a").remove_by_key("c").len(), 0);
+}
+
+#[test]
+fn set_relationship_and_equality() {
+    let v1 = PersistentKeySet::from_intoiter(GET_KEY_FUNC, vec![config("a", 1), config("b", 2)]);
+    let v2 = v1.insert(config("c", 3));
+
+    assert!(v1.is_subset(&v2));
+    assert!(v2.is_superset(&v1));
+    assert!(!v2.is_subset(&v1));
+    assert!(v2.remove_by_key("a").remove_by_key("b").is_disjoint(&v1));
+
+    // equality is by key, whatever the path to the version
+    assert_eq!(v2.remove_by_key("c"), v1);
+    assert_eq!(v1.insert(config("a", 7)), v1);
+    assert_ne!(v2, v1);
+
+    // sets built apart hash differently and still compare
+    let other = PersistentKeySet::from_intoiter(GET_KEY_FUNC, vec![config("b", 0), config("a", 0)]);
+    assert_eq!(other, v1);
+    assert!(other.is_subset(&v2));
+}
+
+#[test]
+fn set_op_new_versions() {
+    let base = PersistentKeySet::from_intoiter(GET_KEY_FUNC, vec![config("a", 1), config("b", 2)]);
+    let v1 = base.insert(config("c", 3));
+    let v2 = base.insert(config("b", 20)).insert(config("d", 4));
+
+    let union = v1.union(&v2);
+    assert_eq!(names(&union), vec!["a", "b", "c", "d"]);
+    assert_eq!(union.get_by_key("b").unwrap().value, 2);
+    assert_eq!(v1.union_by(&v2, Bias::Right).get_by_key("b").unwrap().value, 20);
+
+    let intersection = v1.intersection(&v2);
+    assert_eq!(names(&intersection), vec!["a", "b"]);
+    let merged = v1.intersection_by(&v2, Bias::Merge(|l, r| config(l.name, l.value + r.value)));
+    assert_eq!(merged.get_by_key("b").unwrap().value, 22);
+
+    assert_eq!(names(&v1.difference(&v2)), vec!["c"]);
+    assert_eq!(names(&v1.symmetric_difference(&v2)), vec!["c", "d"]);
+
+    // the operands are untouched
+    assert_eq!(names(&v1), vec!["a", "b", "c"]);
+    assert_eq!(names(&v2), vec!["a", "b", "d"]);
+
+    // a set built apart hashes differently
+    let other = PersistentKeySet::from_intoiter(GET_KEY_FUNC, vec![config("c", 30), config("e", 5)]);
+    assert_eq!(names(&v1.union(&other)), vec!["a", "b", "c", "e"]);
+    assert_eq!(names(&v1.intersection_by(&other, Bias::Right)), vec!["c"]);
+    assert_eq!(v1.intersection_by(&other, Bias::Right).get_by_key("c").unwrap().value, 30);
+    assert_eq!(names(&v1.symmetric_difference(&other)), vec!["a", "b", "e"]);
+    assert_eq!(v1.difference(&other).keys().len(), 2);
+}
+
+#[test]
+fn iter_all_versions() {
+    let mut set = PersistentKeySet::new(debug_key);
+    let mut versions = vec![];
+
+    for i in 0..200 {
+        set = set.insert(i);
+        versions.push(set.clone());
+    }
+
+    for (n, version) in versions.iter().enumerate() {
+        let mut items: Vec<i32> = version.iter().cloned().collect();
+        items.sort_unstable();
+        assert_eq!(items, (0..=n as i32).collect::<Vec<i32>>());
+        assert_eq!(version.iter().len(), n + 1);
+    }
+
+    assert_eq!((&set).into_iter().count(), 200);
+}
+
+/// A key whose hash ignores the last digit, forcing full hash collisions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Coarse(u32);
+
+impl Hash for Coarse {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 / 10).hash(state);
+    }
+}
+
+#[test]
+fn full_hash_collision() {
+    let get_key = |x: &u32| Coarse(*x);
+    let set = PersistentKeySet::from_intoiter_with(get_key, vec![1, 2, 3, 15, 27]);
+
+    assert_eq!(set.len(), 5);
+    assert!(set.contains_key(&Coarse(2)));
+    assert!(!set.contains_key(&Coarse(4)));
+
+    let smaller = set.remove_by_key(&Coarse(2)).remove_by_key(&Coarse(1));
+    assert_eq!(smaller.len(), 3);
+    assert!(smaller.contains_key(&Coarse(3)));
+    assert!(!smaller.contains_key(&Coarse(1)));
+
+    // back to the same keys by another path
+    let rebuilt = smaller.insert(2).insert(1);
+    assert_eq!(rebuilt, set);
+    assert_eq!(rebuilt.insert(3).len(), 5);
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, u8),
+    Remove(u8),
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    prop::collection::vec(
+        prop_oneof![
+            (0u8..64, any::<u8>()).prop_map(|(k, v)| Op::Insert(k, v)),
+            (0u8..64).prop_map(Op::Remove),
+        ],
+        0..200
+    )
+}
+
+proptest! {
+    #[test]
+    fn matches_hashmap(ops in ops()) {
+        let get_key = |x: &(u8, u8)| x.0;
+        let mut set = PersistentKeySet::with_get_key(get_key);
+        let mut oracle = HashMap::new();
+        let mut versions = vec![];
+
+        for op in ops {
+            versions.push((set.clone(), oracle.clone()));
+
+            match op {
+                Op::Insert(k, v) => {
+                    set = set.insert((k, v));
+                    oracle.insert(k, v);
+                },
+                Op::Remove(k) => {
+                    set = set.remove_by_key(&k);
+                    oracle.remove(&k);
+                },
+            }
+        }
+        versions.push((set.clone(), oracle));
+
+        for (version, oracle) in versions.iter() {
+            prop_assert_eq!(version.len(), oracle.len());
+            for (k, v) in oracle.iter() {
+                prop_assert_eq!(version.get_by_key(k), Some(&(*k, *v)));
+            }
+            prop_assert_eq!(version.iter().count(), oracle.len());
+
+            // the trie is canonical, so the same keys inserted in another order
+            // into a version of the same set compare equal node by node
+            let mut rebuilt = set.clone();
+            for k in set.iter().map(|x| x.0).collect::<Vec<u8>>() {
+                rebuilt = rebuilt.remove_by_key(&k);
+            }
+            let mut items: Vec<(u8, u8)> = oracle.iter().map(|(k, v)| (*k, *v)).collect();
+            items.sort_unstable_by(|a, b| b.cmp(a));
+            for item in items {
+                rebuilt = rebuilt.insert(item);
+            }
+            prop_assert!(rebuilt == *version);
+        }
+
+        for pair in versions.windows(2) {
+            let same_keys = pair[0].1.len() == pair[1].1.len()
+                && pair[0].1.keys().all(|k| pair[1].1.contains_key(k));
+            prop_assert_eq!(pair[0].0 == pair[1].0, same_keys);
+        }
+    }
+}
+
+fn elems() -> impl Strategy<Value = Vec<(u8, u8)>> {
+    prop::collection::vec((0u8..64, any::<u8>()), 0..48)
+}
+
+proptest! {
+    #[test]
+    fn set_op_matches_hashset(a in elems(), b in elems(), shared in any::<bool>()) {
+        let get_key = |x: &(u8, u8)| x.0;
+        let a = PersistentKeySet::from_intoiter_with(get_key, a);
+        // a version of `a`, or a set hashing its keys apart
+        let b = match shared {
+            true => b.into_iter().fold(a.clone(), |set, x| if x.1 % 2 == 0 { set.insert(x) } else { set.remove_by_key(&x.0) }),
+            false => PersistentKeySet::from_intoiter_with(get_key, b),
+        };
+        let oracle = |set: &PersistentKeySet<(u8, u8), u8, _>| set.keys().cloned().collect::<HashSet<u8>>();
+        let (oa, ob) = (oracle(&a), oracle(&b));
+
+        for (res, expected) in IntoIterator::into_iter([
+            (a.union(&b), &oa | &ob),
+            (a.intersection(&b), &oa & &ob),
+            (a.difference(&b), &oa - &ob),
+            (a.symmetric_difference(&b), &oa ^ &ob),
+        ]) {
+            prop_assert_eq!(res.len(), expected.len());
+            prop_assert_eq!(oracle(&res), expected);
+        }
+        for v in a.union(&b).iter() {
+            prop_assert_eq!(a.get(v).or_else(|| b.get(v)), Some(v));
+        }
+    }
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use key_set::{ SmallKeySet, KeySet, DuplicatePolicy, InsertOutcome, debug_key };
+
+use common::{ Person, GET_KEY_FUNC, person, sorted_ids };
+
+#[test]
+fn spill_past_inline_capacity() {
+    let mut set: SmallKeySet<Person, u32, 3> = SmallKeySet::new(GET_KEY_FUNC);
+    assert_eq!(SmallKeySet::<Person, u32, 3>::inline_capacity(), 3);
+
+    for id in 0..3 {
+        assert_eq!(set.insert(person(id, "inline")), InsertOutcome::Inserted);
+    }
+    assert!(!set.is_spilled());
+
+    // a known key doesn't spill
+    assert!(matches!(set.insert(person(1, "again")), InsertOutcome::Replaced(_)));
+    assert!(!set.is_spilled());
+
+    assert_eq!(set.insert(person(3, "spilled")), InsertOutcome::Inserted);
+    assert!(set.is_spilled());
+    assert_eq!(sorted_ids(&set), vec![0, 1, 2, 3]);
+    assert_eq!(set.get_by_key(&1).unwrap().name, "again");
+    assert_eq!(set.iter().len(), 4);
+
+    // back inline once it fits
+    assert!(set.remove_by_key(&3));
+    set.shrink_to_fit();
+    assert!(!set.is_spilled());
+    assert_eq!(sorted_ids(&set), vec![0, 1, 2]);
+    assert_eq!(set.get(&person(2, "")).unwrap().name, "inline");
+}
+
+#[test]
+fn inline_removal() {
+    let mut set: SmallKeySet<_, String, 8> = SmallKeySet::from_intoiter(debug_key, 0..6);
+
+    assert!(set.contains_key("3"));
+    assert_eq!(set.take_by_key("0"), Some(0));
+    assert!(!set.remove(&0));
+    assert!(set.remove(&4));
+    assert_eq!(set.len(), 4);
+
+    let mut rest: Vec<i32> = set.iter().cloned().collect();
+    rest.sort_unstable();
+    assert_eq!(rest, vec![1, 2, 3, 5]);
+
+    set.retain(|x| x % 2 == 1);
+    let mut odd: Vec<i32> = set.iter().cloned().collect();
+    odd.sort_unstable();
+    assert_eq!(odd, vec![1, 3, 5]);
+}
+
+#[test]
+fn drain_and_extract() {
+    for spilled in [false, true] {
+        let len = if spilled { 10 } else { 4 };
+        let mut set: SmallKeySet<_, String, 4> = SmallKeySet::from_intoiter(debug_key, 0..len);
+        assert_eq!(set.is_spilled(), spilled);
+
+        let mut even: Vec<i32> = set.extract_if(|x| x % 2 == 0).collect();
+        even.sort_unstable();
+        assert_eq!(even, (0..len).filter(|x| x % 2 == 0).collect::<Vec<i32>>());
+        assert_eq!(set.len(), (len / 2) as usize);
+
+        let drain = set.drain();
+        assert_eq!(drain.len(), (len / 2) as usize);
+        let mut odd: Vec<i32> = drain.collect();
+        odd.sort_unstable();
+        assert_eq!(odd, (0..len).filter(|x| x % 2 == 1).collect::<Vec<i32>>());
+        assert!(set.is_empty());
+
+        set.insert(42);
+        assert_eq!(set.into_iter().collect::<Vec<i32>>(), vec![42]);
+    }
+}
+
+#[test]
+fn extract_if_dropped_early() {
+    let mut set: SmallKeySet<_, String, 8> = SmallKeySet::from_intoiter(debug_key, 0..8);
+
+    // the element swapped into an extracted slot is still visited
+    assert_eq!(set.extract_if(|_| true).take(3).count(), 3);
+    assert_eq!(set.len(), 5);
+}
+
+#[test]
+fn set_op_across_storage() {
+    let small = SmallKeySet::<Person, u32, 2>::from_intoiter(GET_KEY_FUNC, vec![person(1, "a"), person(2, "b")]);
+    let large = SmallKeySet::<Person, u32, 2>::from_intoiter(GET_KEY_FUNC, (2..6).map(|id| person(id, "l")));
+    assert!(!small.is_spilled());
+    assert!(large.is_spilled());
+
+    assert_eq!(sorted_ids(&small.union(&large)), vec![1, 2, 3, 4, 5]);
+    assert_eq!(small.intersection(&large).get_by_key(&2).unwrap().name, "b");
+    assert_eq!(sorted_ids(&large.difference(&small)), vec![3, 4, 5]);
+    assert_eq!(sorted_ids(&small.symmetric_difference(&large)), vec![1, 3, 4, 5]);
+
+    let mut merged = small.union(&large);
+    merged.shrink_to_fit();
+    assert_eq!(merged, small.union(&large));
+
+    let mut left = small;
+    left.symmetric_difference_with(large);
+    assert_eq!(sorted_ids(&left), vec![1, 3, 4, 5]);
+}
+
+#[test]
+fn duplicate_policy() {
+    let mut set: SmallKeySet<Person, u32, 4> = SmallKeySet::new(GET_KEY_FUNC);
+    set.set_duplicate_policy(DuplicatePolicy::KeepFirst);
+
+    set.insert(person(1, "first"));
+    assert!(matches!(set.insert(person(1, "second")), InsertOutcome::Kept(_)));
+    assert_eq!(set.get_by_key(&1).unwrap().name, "first");
+
+    assert!(set.try_insert(person(1, "third")).is_err());
+    assert_eq!(set.try_insert(person(2, "two")).unwrap().name, "two");
+    assert_eq!(format!("{:?}", set), r#"{1: Person { id: 1, name: "first", phone: 0 }, 2: Person { id: 2, name: "two", phone: 0 }}"#);
+}
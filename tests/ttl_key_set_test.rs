@@ -0,0 +1,181 @@
+mod common;
+
+use std::time::Duration;
+
+use key_set::{ TtlKeySet, ManualClock, debug_key };
+
+use common::{ GET_KEY_FUNC, person, sorted_ids };
+
+fn secs(n: u64) -> Duration {
+    Duration::from_secs(n)
+}
+
+#[test]
+fn expired_elements_are_invisible() {
+    let clock = ManualClock::new();
+    let mut set = TtlKeySet::with_clock(GET_KEY_FUNC, secs(10), clock.clone());
+
+    assert_eq!(set.insert(person(1, "alice")), None);
+    assert_eq!(set.insert_with_ttl(person(2, "bob"), secs(5)), None);
+    assert_eq!(set.insert_with_ttl(person(3, "carol"), secs(30)), None);
+    assert_eq!(sorted_ids(&set), vec![1, 2, 3]);
+    assert_eq!(set.remaining_ttl(&2), Some(secs(5)));
+
+    clock.advance(secs(5));
+    assert!(!set.contains_key(&2));
+    assert!(set.get(&person(2, "")).is_none());
+    assert_eq!(set.remaining_ttl(&2), None);
+    assert_eq!(set.get_by_key(&1).unwrap().name, "alice");
+    assert_eq!(sorted_ids(&set), vec![1, 3]);
+    assert_eq!(set.len(), 2);
+    // still held until purged
+    assert_eq!(set.stored_len(), 3);
+
+    clock.advance(secs(5));
+    assert!(!set.contains(&person(1, "")));
+    assert_eq!(sorted_ids(&set), vec![3]);
+    assert_eq!(format!("{:?}", set), r#"{Person { id: 3, name: "carol", phone: 0 }}"#);
+
+    clock.advance(secs(20));
+    assert!(set.is_empty());
+}
+
+#[test]
+fn insert_replaces_and_renews() {
+    let clock = ManualClock::new();
+    let mut set = TtlKeySet::with_clock(GET_KEY_FUNC, secs(10), clock.clone());
+
+    set.insert(person(1, "alice"));
+    clock.advance(secs(8));
+
+    // a live element is handed back and the deadline starts over
+    assert_eq!(set.insert(person(1, "alice2")).unwrap().name, "alice");
+    clock.advance(secs(8));
+    assert_eq!(set.get_by_key(&1).unwrap().name, "alice2");
+
+    // an expired one is dropped silently
+    clock.advance(secs(2));
+    assert_eq!(set.insert(person(1, "alice3")), None);
+    assert_eq!(set.stored_len(), 1);
+
+    // the renewed element outlives the deadline of the replaced one
+    assert_eq!(set.purge_expired(), 0);
+    assert_eq!(set.get_by_key(&1).unwrap().name, "alice3");
+}
+
+#[test]
+fn purge_expired() {
+    let clock = ManualClock::new();
+    let mut set = TtlKeySet::with_clock(debug_key, secs(10), clock.clone());
+
+    for x in 0..10 {
+        set.insert_with_ttl(x, secs(x as u64 + 1));
+    }
+    assert_eq!(set.purge_expired(), 0);
+
+    clock.advance(secs(4));
+    assert_eq!(set.purge_expired(), 4);
+    assert_eq!(set.stored_len(), 6);
+    assert!(!set.contains_key("2"));
+
+    // removed and replaced elements aren't counted twice
+    assert!(set.remove_by_key("5"));
+    set.insert_with_ttl(6, secs(100));
+    clock.advance(secs(4));
+    assert_eq!(set.purge_expired(), 2);
+
+    let mut rest: Vec<i32> = set.iter().cloned().collect();
+    rest.sort_unstable();
+    assert_eq!(rest, vec![6, 8, 9]);
+
+    // freed slots are reused
+    set.insert(20);
+    assert_eq!(set.stored_len(), 4);
+
+    clock.advance(secs(1000));
+    assert_eq!(set.purge_expired(), 4);
+    assert_eq!(set.stored_len(), 0);
+}
+
+#[test]
+fn many_replacements_keep_heap_bounded() {
+    let clock = ManualClock::new();
+    let mut set = TtlKeySet::with_clock(debug_key, secs(10), clock.clone());
+
+    for round in 0..1000 {
+        set.insert(round % 3);
+    }
+    assert_eq!(set.len(), 3);
+
+    clock.advance(secs(10));
+    assert_eq!(set.purge_expired(), 3);
+    assert!(set.is_empty());
+}
+
+#[test]
+fn ttl_too_long_never_expires() {
+    let clock = ManualClock::new();
+    let mut set = TtlKeySet::with_clock(GET_KEY_FUNC, Duration::MAX, clock.clone());
+
+    set.insert(person(1, "alice"));
+    set.insert_with_ttl(person(2, "bob"), secs(5));
+    assert_eq!(set.remaining_ttl(&1), Some(Duration::MAX));
+
+    clock.advance(secs(1_000_000));
+    assert_eq!(set.purge_expired(), 1);
+    assert_eq!(sorted_ids(&set), vec![1]);
+
+    // a finite TTL brings the deadline back
+    set.insert_with_ttl(person(1, "alice"), secs(5));
+    clock.advance(secs(5));
+    assert_eq!(set.purge_expired(), 1);
+    assert!(set.is_empty());
+}
+
+#[test]
+fn manual_clock_saturates() {
+    let clock = ManualClock::new();
+    let mut set = TtlKeySet::with_clock(GET_KEY_FUNC, secs(1_000_000), clock.clone());
+    set.insert(person(1, "alice"));
+
+    // the time never wraps back past a deadline
+    clock.advance(secs(10));
+    clock.advance(Duration::MAX);
+    assert!(set.is_empty());
+
+    clock.advance(secs(10));
+    assert!(set.is_empty());
+    assert_eq!(set.purge_expired(), 1);
+}
+
+#[test]
+fn take_and_clear() {
+    let clock = ManualClock::new();
+    let mut set = TtlKeySet::with_clock(GET_KEY_FUNC, secs(10), clock.clone());
+
+    set.insert(person(1, "alice"));
+    set.insert_with_ttl(person(2, "bob"), secs(1));
+
+    assert_eq!(set.take_by_key(&1), Some(person(1, "alice")));
+    assert_eq!(set.take_by_key(&1), None);
+
+    // an expired element is removed but not handed back
+    clock.advance(secs(1));
+    assert!(!set.remove(&person(2, "")));
+    assert_eq!(set.stored_len(), 0);
+
+    set.insert(person(3, "carol"));
+    set.clear();
+    assert!(set.is_empty());
+    assert_eq!(set.purge_expired(), 0);
+}
+
+#[test]
+fn system_clock() {
+    let mut set = TtlKeySet::new(GET_KEY_FUNC, secs(3600));
+
+    set.insert(person(1, "alice"));
+    assert!(set.contains_key(&1));
+    assert_eq!((&set).into_iter().count(), 1);
+    assert!(set.remaining_ttl(&1).unwrap() <= secs(3600));
+}